@@ -0,0 +1,484 @@
+use std::{
+    collections::HashMap,
+    env, fmt,
+    sync::{Arc, RwLock},
+};
+
+use alloy::{
+    network::{EthereumWallet, TransactionBuilder as _},
+    primitives::{Address, B256, Bytes, U256},
+    providers::{Provider as _, ProviderBuilder},
+    rpc::types::TransactionRequest,
+    signers::local::PrivateKeySigner,
+    sol_types::SolCall,
+};
+use anyhow::{Context as _, Result, bail};
+use axum::{Json, debug_handler, extract::State, http::StatusCode, response::IntoResponse};
+use futures_util::future::BoxFuture;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, instrument, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    api::{ApiError, ErrorBody, JsonBody, error_response},
+    auth::UserSession,
+    bindings::IPokerTable,
+    privy::{Privy, PrivyConfig, PrivyError},
+    state::AppState,
+    telemetry::DELEGATED_ACTIONS,
+};
+
+/// A contract call to send from the wallet of a player.
+#[derive(Debug, Clone)]
+pub struct DelegatedTx {
+    pub chain_id: u64,
+    pub to: Address,
+    pub data: Bytes,
+}
+
+/// Sends transactions from the wallets which the players delegated to the backend.
+pub trait DelegatedWallets: fmt::Debug + Send + Sync {
+    /// Send a transaction from a wallet of the session, returning its hash without waiting for it
+    /// to be mined.
+    fn send<'a>(
+        &'a self,
+        session: &'a UserSession,
+        wallet: Address,
+        tx: DelegatedTx,
+    ) -> BoxFuture<'a, Result<B256, ActionError>>;
+}
+
+/// Build the delegated wallets selected by the `DELEGATED_WALLETS` environment variable, `privy` or
+/// `local`, returning `None` if it is not set.
+pub fn wallets_from_env(rpc_url: &str) -> Result<Option<Arc<dyn DelegatedWallets>>> {
+    match env::var("DELEGATED_WALLETS").as_deref().unwrap_or_default() {
+        "" => Ok(None),
+        "privy" => Ok(Some(Arc::new(Privy::new(PrivyConfig::from_env()?)?))),
+        "local" => {
+            warn!("using local delegated wallets, transactions are signed with local keys");
+            Ok(Some(Arc::new(LocalWallets::from_env(rpc_url)?)))
+        }
+        other => bail!("unknown DELEGATED_WALLETS {other}, expected privy or local"),
+    }
+}
+
+/// Stand-in for the Privy server wallets in development and tests, which signs the transactions
+/// with the private keys listed in `LOCAL_DELEGATED_KEYS`, for any session using their wallet.
+#[derive(Clone)]
+pub struct LocalWallets {
+    rpc_url: String,
+    signers: HashMap<Address, PrivateKeySigner>,
+}
+
+impl fmt::Debug for LocalWallets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalWallets")
+            .field("rpc_url", &self.rpc_url)
+            .field("wallets", &self.signers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl LocalWallets {
+    pub fn from_env(rpc_url: &str) -> Result<Self> {
+        let signers = env::var("LOCAL_DELEGATED_KEYS")
+            .context("LOCAL_DELEGATED_KEYS environment variable")?
+            .split(',')
+            .filter(|key| !key.trim().is_empty())
+            .map(|key| {
+                let signer: PrivateKeySigner = key.trim().parse()?;
+                Ok((signer.address(), signer))
+            })
+            .collect::<Result<HashMap<_, _>>>()
+            .context("LOCAL_DELEGATED_KEYS environment variable")?;
+        Ok(Self {
+            rpc_url: rpc_url.to_string(),
+            signers,
+        })
+    }
+}
+
+impl DelegatedWallets for LocalWallets {
+    fn send<'a>(
+        &'a self,
+        _session: &'a UserSession,
+        wallet: Address,
+        tx: DelegatedTx,
+    ) -> BoxFuture<'a, Result<B256, ActionError>> {
+        Box::pin(async move {
+            let signer = self
+                .signers
+                .get(&wallet)
+                .ok_or(ActionError::NotDelegated(wallet))?;
+            let url = self
+                .rpc_url
+                .parse()
+                .map_err(|err| ActionError::Failed(anyhow::Error::new(err)))?;
+            let provider = ProviderBuilder::new()
+                .wallet(EthereumWallet::from(signer.clone()))
+                .on_http(url);
+            let request = TransactionRequest::default()
+                .with_chain_id(tx.chain_id)
+                .with_to(tx.to)
+                .with_input(tx.data);
+            let pending = provider
+                .send_transaction(request)
+                .await
+                .map_err(|err| ActionError::Failed(err.into()))?;
+            Ok(*pending.tx_hash())
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BetRequest {
+    /// The amount to bet, in wei
+    #[schema(value_type = String)]
+    pub amount: U256,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SubmittedAction {
+    /// The contract function which was called
+    pub call: &'static str,
+
+    /// The wallet which sent the transaction
+    #[schema(value_type = String)]
+    pub wallet: Address,
+
+    /// The hash of the transaction, which is not mined yet
+    #[schema(value_type = String)]
+    pub hash: B256,
+}
+
+#[utoipa::path(
+    post,
+    path = "/actions/bet",
+    tag = "actions",
+    security(("bearer" = [])),
+    request_body = BetRequest,
+    responses(
+        (status = 202, description = "The bet was sent from the delegated wallet of the player", body = SubmittedAction),
+        (status = 401, description = "Missing or invalid access token", body = ErrorBody),
+        (status = 403, description = "The wallet of the player is not delegated", body = ErrorBody),
+        (status = 404, description = "The player is not seated at the table", body = ErrorBody),
+        (status = 502, description = "The transaction could not be sent", body = ErrorBody),
+        (status = 503, description = "Delegated actions are not enabled", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn bet(
+    session: UserSession,
+    State(state): State<Arc<RwLock<AppState>>>,
+    JsonBody(request): JsonBody<BetRequest>,
+) -> Result<(StatusCode, Json<SubmittedAction>), ActionError> {
+    info!("endpoint called");
+    let call = IPokerTable::betCall {
+        amount: request.amount,
+    };
+    submit(&state, &session, "bet", call.abi_encode()).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/actions/fold",
+    tag = "actions",
+    security(("bearer" = [])),
+    responses(
+        (status = 202, description = "The fold was sent from the delegated wallet of the player", body = SubmittedAction),
+        (status = 401, description = "Missing or invalid access token", body = ErrorBody),
+        (status = 403, description = "The wallet of the player is not delegated", body = ErrorBody),
+        (status = 404, description = "The player is not seated at the table", body = ErrorBody),
+        (status = 502, description = "The transaction could not be sent", body = ErrorBody),
+        (status = 503, description = "Delegated actions are not enabled", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn fold(
+    session: UserSession,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<(StatusCode, Json<SubmittedAction>), ActionError> {
+    info!("endpoint called");
+    submit(
+        &state,
+        &session,
+        "fold",
+        IPokerTable::foldCall {}.abi_encode(),
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/actions/leave",
+    tag = "actions",
+    security(("bearer" = [])),
+    responses(
+        (status = 202, description = "The player leaving the table was sent from their delegated wallet", body = SubmittedAction),
+        (status = 401, description = "Missing or invalid access token", body = ErrorBody),
+        (status = 403, description = "The wallet of the player is not delegated", body = ErrorBody),
+        (status = 404, description = "The player is not seated at the table", body = ErrorBody),
+        (status = 502, description = "The transaction could not be sent", body = ErrorBody),
+        (status = 503, description = "Delegated actions are not enabled", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn leave(
+    session: UserSession,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<(StatusCode, Json<SubmittedAction>), ActionError> {
+    info!("endpoint called");
+    submit(
+        &state,
+        &session,
+        "leave_table",
+        IPokerTable::leaveTableCall {}.abi_encode(),
+    )
+    .await
+}
+
+/// Send a call to the table from the seated wallet of the session.
+async fn submit(
+    state: &Arc<RwLock<AppState>>,
+    session: &UserSession,
+    call: &'static str,
+    data: Vec<u8>,
+) -> Result<(StatusCode, Json<SubmittedAction>), ActionError> {
+    let table = {
+        let state = state.read().expect("state lock should not be poisoned");
+        ActionTable {
+            wallets: state.delegated_wallets.clone(),
+            seated: state.table_players.iter().map(|p| p.address).collect(),
+            chain_id: state.listener.chain_id,
+            address: state.table_address,
+        }
+    };
+    let submitted = table.submit(session, call, data).await?;
+    Ok((StatusCode::ACCEPTED, Json(submitted)))
+}
+
+/// The table which the actions are sent to, as seen when they are submitted.
+struct ActionTable {
+    wallets: Option<Arc<dyn DelegatedWallets>>,
+
+    /// The wallets seated at the table
+    seated: Vec<Address>,
+    chain_id: Option<u64>,
+    address: Address,
+}
+
+impl ActionTable {
+    async fn submit(
+        &self,
+        session: &UserSession,
+        call: &'static str,
+        data: Vec<u8>,
+    ) -> Result<SubmittedAction, ActionError> {
+        let wallets = self.wallets.as_ref().ok_or(ActionError::Disabled)?;
+        let wallet = session.wallet_for(|wallet| self.seated.contains(&wallet));
+        if !self.seated.contains(&wallet) {
+            return Err(ActionError::NotSeated(wallet));
+        }
+        let tx = DelegatedTx {
+            chain_id: self.chain_id.ok_or(ActionError::ChainUnknown)?,
+            to: self.address,
+            data: data.into(),
+        };
+        let hash = wallets.send(session, wallet, tx).await.inspect_err(|_| {
+            counter!(DELEGATED_ACTIONS, "call" => call, "status" => "failed").increment(1);
+        })?;
+        counter!(DELEGATED_ACTIONS, "call" => call, "status" => "sent").increment(1);
+        info!(?wallet, %hash, call, "sent delegated action");
+        Ok(SubmittedAction { call, wallet, hash })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum ActionError {
+    #[error("delegated actions are not enabled")]
+    Disabled,
+
+    #[error("chain of the table is not known yet")]
+    ChainUnknown,
+
+    #[error("player not seated: {0}")]
+    NotSeated(Address),
+
+    #[error("wallet is not delegated: {0}")]
+    NotDelegated(Address),
+
+    #[error("failed to send transaction: {0:#}")]
+    Failed(anyhow::Error),
+
+    #[error(transparent)]
+    Privy(#[from] PrivyError),
+}
+
+impl ApiError for ActionError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ActionError::Disabled | ActionError::ChainUnknown => StatusCode::SERVICE_UNAVAILABLE,
+            ActionError::NotSeated(_) => StatusCode::NOT_FOUND,
+            ActionError::NotDelegated(_) => StatusCode::FORBIDDEN,
+            ActionError::Failed(_) => StatusCode::BAD_GATEWAY,
+            ActionError::Privy(err) => err.status(),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            ActionError::Disabled => "delegated_actions_disabled",
+            ActionError::ChainUnknown => "chain_unknown",
+            ActionError::NotSeated(_) => "player_not_seated",
+            ActionError::NotDelegated(_) => "wallet_not_delegated",
+            ActionError::Failed(_) => "action_failed",
+            ActionError::Privy(err) => err.code(),
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            ActionError::NotSeated(wallet) | ActionError::NotDelegated(wallet) => {
+                Some(json!({ "wallet": wallet }))
+            }
+            ActionError::Privy(err) => err.details(),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for ActionError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Records the transactions instead of sending them, from the delegated wallets only.
+    #[derive(Debug, Default)]
+    struct FakeWallets {
+        delegated: Vec<Address>,
+        sent: Mutex<Vec<(String, Address, DelegatedTx)>>,
+    }
+
+    impl DelegatedWallets for FakeWallets {
+        fn send<'a>(
+            &'a self,
+            session: &'a UserSession,
+            wallet: Address,
+            tx: DelegatedTx,
+        ) -> BoxFuture<'a, Result<B256, ActionError>> {
+            Box::pin(async move {
+                if !self.delegated.contains(&wallet) {
+                    return Err(ActionError::NotDelegated(wallet));
+                }
+                let mut sent = self.sent.lock().unwrap();
+                sent.push((session.session_id.clone(), wallet, tx));
+                Ok(B256::with_last_byte(u8::try_from(sent.len()).unwrap()))
+            })
+        }
+    }
+
+    const PRIMARY: Address = Address::repeat_byte(1);
+    const SEATED: Address = Address::repeat_byte(2);
+    const TABLE: Address = Address::repeat_byte(0xaa);
+
+    fn session() -> UserSession {
+        UserSession::new(
+            "user".to_string(),
+            "session".to_string(),
+            vec![PRIMARY, SEATED],
+        )
+    }
+
+    fn table(wallets: &Arc<FakeWallets>, seated: Vec<Address>) -> ActionTable {
+        ActionTable {
+            wallets: Some(Arc::clone(wallets) as Arc<dyn DelegatedWallets>),
+            seated,
+            chain_id: Some(1),
+            address: TABLE,
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_from_the_seated_wallet() {
+        let wallets = Arc::new(FakeWallets {
+            delegated: vec![PRIMARY, SEATED],
+            ..FakeWallets::default()
+        });
+        let submitted = table(&wallets, vec![SEATED])
+            .submit(&session(), "fold", vec![0xab])
+            .await
+            .unwrap();
+        assert_eq!(submitted.wallet, SEATED);
+        assert_eq!(submitted.call, "fold");
+        assert_eq!(submitted.hash, B256::with_last_byte(1));
+        let sent = wallets.sent.lock().unwrap();
+        let [(session_id, wallet, tx)] = sent.as_slice() else {
+            panic!("expected one transaction, got {sent:?}");
+        };
+        assert_eq!(session_id, "session");
+        assert_eq!(*wallet, SEATED);
+        assert_eq!((tx.chain_id, tx.to), (1, TABLE));
+        assert_eq!(tx.data, Bytes::from(vec![0xab]));
+    }
+
+    #[tokio::test]
+    async fn not_seated() {
+        let wallets = Arc::new(FakeWallets {
+            delegated: vec![PRIMARY, SEATED],
+            ..FakeWallets::default()
+        });
+        let err = table(&wallets, vec![Address::repeat_byte(3)])
+            .submit(&session(), "fold", vec![])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ActionError::NotSeated(wallet) if wallet == PRIMARY),
+            "{err:?}"
+        );
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+        assert!(wallets.sent.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn not_delegated() {
+        let wallets = Arc::new(FakeWallets {
+            delegated: vec![PRIMARY],
+            ..FakeWallets::default()
+        });
+        let err = table(&wallets, vec![SEATED])
+            .submit(&session(), "fold", vec![])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ActionError::NotDelegated(wallet) if wallet == SEATED),
+            "{err:?}"
+        );
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let table = ActionTable {
+            wallets: None,
+            seated: vec![SEATED],
+            chain_id: Some(1),
+            address: TABLE,
+        };
+        let err = table.submit(&session(), "fold", vec![]).await.unwrap_err();
+        assert!(matches!(err, ActionError::Disabled), "{err:?}");
+        assert_eq!(err.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
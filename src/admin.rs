@@ -0,0 +1,644 @@
+use std::{
+    env, fmt,
+    sync::{Arc, RwLock},
+};
+
+use alloy::primitives::{Address, B256, U256};
+use anyhow::{Context as _, Result};
+use axum::{
+    Json, debug_handler,
+    extract::{FromRequestParts, State},
+    http::{StatusCode, request::Parts},
+    response::IntoResponse,
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use chrono::Utc;
+use rs_poker::core::Card;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::oneshot;
+use tracing::{error, info, instrument, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    api::{ApiError, ErrorBody, JsonBody, error_response},
+    api_keys::revoke_wallet_keys,
+    auth::{AuthError, UserSession},
+    db::{Store, StoreError},
+    openapi::CardSchema,
+    sessions::{ActiveSession, persist_revocations},
+    state::{AppState, DealerTx, TablePlayer},
+};
+
+/// Maximum number of admin commands waiting to be processed by the listener.
+pub const ADMIN_COMMANDS_CAPACITY: usize = 8;
+
+/// Table of the audit log.
+pub(crate) const AUDIT_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS admin_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    at INTEGER NOT NULL,
+    operator TEXT NOT NULL,
+    action TEXT NOT NULL,
+    tx_hash TEXT,
+    error TEXT
+);
+";
+
+/// Credentials allowed to use the admin API.
+#[derive(Clone)]
+pub struct AdminConfig {
+    /// Static token for operators, sent as a bearer token
+    pub token: Option<String>,
+
+    /// Wallets of the operators, authenticated with Privy
+    pub wallets: Vec<Address>,
+}
+
+impl AdminConfig {
+    pub fn from_env() -> Result<Self> {
+        let token = env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
+        let wallets = env::var("ADMIN_WALLETS")
+            .ok()
+            .map(|wallets| {
+                wallets
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|w| !w.is_empty())
+                    .map(str::parse)
+                    .collect::<Result<Vec<Address>, _>>()
+            })
+            .transpose()
+            .context("ADMIN_WALLETS environment variable")?
+            .unwrap_or_default();
+        if token.is_none() && wallets.is_empty() {
+            warn!("no admin token nor operator wallets configured, the admin API is disabled");
+        }
+        Ok(Self { token, wallets })
+    }
+}
+
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("wallets", &self.wallets)
+            .finish()
+    }
+}
+
+/// An authenticated operator of the table.
+#[derive(Debug, Clone)]
+pub enum Operator {
+    /// Authenticated with the static admin token
+    Token,
+
+    /// Authenticated with Privy, with a wallet from the allowlist
+    Wallet(Address),
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Token => write!(f, "admin-token"),
+            Operator::Wallet(address) => write!(f, "{address}"),
+        }
+    }
+}
+
+impl FromRequestParts<Arc<RwLock<AppState>>> for Operator {
+    type Rejection = AdminError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<RwLock<AppState>>,
+    ) -> Result<Self, Self::Rejection> {
+        let TypedHeader(Authorization(bearer)) = parts
+            .extract::<TypedHeader<Authorization<Bearer>>>()
+            .await
+            .map_err(|_| AdminError::Unauthorized)?;
+        let config = {
+            state
+                .read()
+                .expect("lock should not be poisoned")
+                .admin
+                .clone()
+        };
+        if config
+            .token
+            .as_deref()
+            .is_some_and(|token| constant_time_eq(token, bearer.token()))
+        {
+            return Ok(Operator::Token);
+        }
+        if config.wallets.is_empty() {
+            return Err(AdminError::Unauthorized);
+        }
+        let session = UserSession::from_request_parts(parts, state).await?;
+        let wallet = session.wallet_for(|wallet| config.wallets.contains(&wallet));
+        if config.wallets.contains(&wallet) {
+            Ok(Operator::Wallet(wallet))
+        } else {
+            Err(AdminError::NotAnOperator(wallet))
+        }
+    }
+}
+
+/// Compare secrets without leaking the position of the first difference through timing.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// Actions which operators can take on the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminAction {
+    ShowPhase,
+    CancelRound,
+    TimeoutPlayer,
+    PauseAutoStart,
+    ResumeAutoStart,
+    Resync,
+    ListSessions,
+    RevokeSessions,
+}
+
+impl AdminAction {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            AdminAction::ShowPhase => "show_phase",
+            AdminAction::CancelRound => "cancel_round",
+            AdminAction::TimeoutPlayer => "timeout_player",
+            AdminAction::PauseAutoStart => "pause_auto_start",
+            AdminAction::ResumeAutoStart => "resume_auto_start",
+            AdminAction::Resync => "resync",
+            AdminAction::ListSessions => "list_sessions",
+            AdminAction::RevokeSessions => "revoke_sessions",
+        }
+    }
+}
+
+/// A command for the listener, which owns the connection to the table contract.
+#[derive(Debug)]
+pub struct AdminCommand {
+    pub action: AdminAction,
+
+    /// Receives the transaction sent by the dealer to carry out the action, if any
+    pub reply: oneshot::Sender<Result<Option<DealerTx>>>,
+}
+
+/// An entry of the audit log of the admin actions.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    /// Unix timestamp of the action
+    pub at: i64,
+    pub operator: String,
+    pub action: AdminAction,
+
+    /// The transaction sent by the dealer, if any
+    pub tx_hash: Option<B256>,
+
+    /// Why the action failed, if it did
+    pub error: Option<String>,
+}
+
+impl Store {
+    /// Append an admin action to the audit log.
+    pub fn record_audit(&self, entry: &AuditEntry) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO admin_audit (at, operator, action, tx_hash, error) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                entry.at,
+                entry.operator,
+                entry.action.name(),
+                entry.tx_hash.map(|hash| hash.to_string()),
+                entry.error,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminOutcome {
+    pub action: AdminAction,
+
+    /// The transaction sent by the dealer to carry out the action, if any
+    pub tx: Option<DealerTx>,
+}
+
+/// Internal state of the dealer for the current round, without the hole cards.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PhaseView {
+    pub phase: &'static str,
+    #[schema(value_type = Option<String>)]
+    pub round_id: Option<U256>,
+
+    /// The players dealt into the round
+    pub players: Vec<TablePlayer>,
+    #[schema(value_type = Vec<CardSchema>)]
+    pub board: Vec<Card>,
+
+    /// Number of cards left in the deck
+    pub deck_size: Option<usize>,
+
+    /// Whether a new round starts as soon as enough players are seated
+    pub auto_start: bool,
+    pub dealer_txs: Vec<DealerTx>,
+    pub last_processed_block: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/phase",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The internal state of the dealer", body = PhaseView),
+        (status = 401, description = "Missing or invalid admin credentials", body = ErrorBody),
+        (status = 403, description = "The wallet is not an operator", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn show_phase(
+    operator: Operator,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Json<PhaseView> {
+    info!("endpoint called");
+    let view = {
+        let state = state.read().expect("state lock should not be poisoned");
+        PhaseView {
+            phase: state.phase.name(),
+            round_id: state.round.id,
+            players: state
+                .get_players()
+                .map(|players| {
+                    players
+                        .iter()
+                        .map(|p| TablePlayer {
+                            address: p.address,
+                            seat: p.seat,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            board: state.get_board(),
+            deck_size: state.get_deck().map(|deck| deck.len()),
+            auto_start: state.auto_start,
+            dealer_txs: state.round.dealer_txs.clone(),
+            last_processed_block: state.last_processed_block,
+        }
+    };
+    audit(&state, &operator, AdminAction::ShowPhase, &Ok(None)).await;
+    Json(view)
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/round/cancel",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The round was cancelled with `cancelCurrentRound`", body = AdminOutcome),
+        (status = 401, description = "Missing or invalid admin credentials", body = ErrorBody),
+        (status = 403, description = "The wallet is not an operator", body = ErrorBody),
+        (status = 502, description = "The transaction could not be sent", body = ErrorBody),
+        (status = 503, description = "The listener is not running", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn cancel_round(
+    operator: Operator,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<AdminOutcome>, AdminError> {
+    info!("endpoint called");
+    run(&state, &operator, AdminAction::CancelRound).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/round/timeout",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The current player was timed out with `timeoutCurrentPlayer`", body = AdminOutcome),
+        (status = 401, description = "Missing or invalid admin credentials", body = ErrorBody),
+        (status = 403, description = "The wallet is not an operator", body = ErrorBody),
+        (status = 502, description = "The transaction could not be sent", body = ErrorBody),
+        (status = 503, description = "The listener is not running", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn timeout_player(
+    operator: Operator,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<AdminOutcome>, AdminError> {
+    info!("endpoint called");
+    run(&state, &operator, AdminAction::TimeoutPlayer).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/auto-start/pause",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "New rounds are not started anymore", body = AdminOutcome),
+        (status = 401, description = "Missing or invalid admin credentials", body = ErrorBody),
+        (status = 403, description = "The wallet is not an operator", body = ErrorBody),
+        (status = 503, description = "The listener is not running", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn pause_auto_start(
+    operator: Operator,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<AdminOutcome>, AdminError> {
+    info!("endpoint called");
+    run(&state, &operator, AdminAction::PauseAutoStart).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/auto-start/resume",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "New rounds are started again, right away if enough players are seated", body = AdminOutcome),
+        (status = 401, description = "Missing or invalid admin credentials", body = ErrorBody),
+        (status = 403, description = "The wallet is not an operator", body = ErrorBody),
+        (status = 502, description = "The transaction could not be sent", body = ErrorBody),
+        (status = 503, description = "The listener is not running", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn resume_auto_start(
+    operator: Operator,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<AdminOutcome>, AdminError> {
+    info!("endpoint called");
+    run(&state, &operator, AdminAction::ResumeAutoStart).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/resync",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The state was reloaded from the contract, cancelling the round if it could not be resumed", body = AdminOutcome),
+        (status = 401, description = "Missing or invalid admin credentials", body = ErrorBody),
+        (status = 403, description = "The wallet is not an operator", body = ErrorBody),
+        (status = 502, description = "The contract could not be queried", body = ErrorBody),
+        (status = 503, description = "The listener is not running", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn resync(
+    operator: Operator,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<AdminOutcome>, AdminError> {
+    info!("endpoint called");
+    run(&state, &operator, AdminAction::Resync).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/sessions",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The sessions which made requests in the last 24 hours", body = Vec<ActiveSession>),
+        (status = 401, description = "Missing or invalid admin credentials", body = ErrorBody),
+        (status = 403, description = "The wallet is not an operator", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn list_sessions(
+    operator: Operator,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Json<Vec<ActiveSession>> {
+    info!("endpoint called");
+    let sessions = {
+        state
+            .read()
+            .expect("state lock should not be poisoned")
+            .sessions
+            .list()
+    };
+    audit(&state, &operator, AdminAction::ListSessions, &Ok(None)).await;
+    Json(sessions)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RevokeRequest {
+    /// The session to revoke
+    pub session_id: Option<String>,
+
+    /// Revoke all the active sessions and API keys of this wallet
+    #[schema(value_type = Option<String>)]
+    pub wallet: Option<Address>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevokeOutcome {
+    /// The IDs of the revoked sessions
+    pub revoked: Vec<String>,
+
+    /// The IDs of the revoked API keys, when revoking a wallet
+    pub revoked_api_keys: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/sessions/revoke",
+    tag = "admin",
+    security(("bearer" = [])),
+    request_body = RevokeRequest,
+    responses(
+        (status = 200, description = "The sessions and API keys were revoked, their tokens are not accepted anymore", body = RevokeOutcome),
+        (status = 400, description = "Neither a session nor a wallet was given", body = ErrorBody),
+        (status = 401, description = "Missing or invalid admin credentials", body = ErrorBody),
+        (status = 403, description = "The wallet is not an operator", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn revoke_sessions(
+    operator: Operator,
+    State(state): State<Arc<RwLock<AppState>>>,
+    JsonBody(request): JsonBody<RevokeRequest>,
+) -> Result<Json<RevokeOutcome>, AdminError> {
+    info!("endpoint called");
+    if request.session_id.is_none() && request.wallet.is_none() {
+        return Err(AdminError::InvalidRequest(
+            "expected a session_id or a wallet".to_string(),
+        ));
+    }
+    let revoked = {
+        state
+            .read()
+            .expect("state lock should not be poisoned")
+            .sessions
+            .revoke(request.session_id.as_deref(), request.wallet)
+    };
+    info!(%operator, ?revoked, "revoked sessions");
+    persist_revocations(&state, revoked.clone(), operator.to_string()).await;
+    let revoked_api_keys = match request.wallet {
+        Some(wallet) => revoke_wallet_keys(&state, wallet).await,
+        None => vec![],
+    };
+    if !revoked_api_keys.is_empty() {
+        info!(%operator, ?revoked_api_keys, "revoked API keys");
+    }
+    audit(&state, &operator, AdminAction::RevokeSessions, &Ok(None)).await;
+    Ok(Json(RevokeOutcome {
+        revoked,
+        revoked_api_keys,
+    }))
+}
+
+/// Send a command to the listener, wait for its outcome and audit it.
+async fn run(
+    state: &Arc<RwLock<AppState>>,
+    operator: &Operator,
+    action: AdminAction,
+) -> Result<Json<AdminOutcome>, AdminError> {
+    let commands = {
+        state
+            .read()
+            .expect("state lock should not be poisoned")
+            .admin_commands
+            .clone()
+    };
+    let (reply, outcome) = oneshot::channel();
+    let outcome = async {
+        commands
+            .send(AdminCommand { action, reply })
+            .await
+            .map_err(|_| AdminError::ListenerUnavailable)?;
+        outcome
+            .await
+            .map_err(|_| AdminError::ListenerUnavailable)?
+            .map_err(|err| AdminError::Failed(format!("{err:#}")))
+    }
+    .await;
+    audit(state, operator, action, &outcome).await;
+    outcome.map(|tx| Json(AdminOutcome { action, tx }))
+}
+
+/// Record an admin action to the audit log.
+///
+/// The entry is both logged with the `audit` target and persisted to the database.
+async fn audit(
+    state: &Arc<RwLock<AppState>>,
+    operator: &Operator,
+    action: AdminAction,
+    outcome: &Result<Option<DealerTx>, AdminError>,
+) {
+    let entry = AuditEntry {
+        at: Utc::now().timestamp(),
+        operator: operator.to_string(),
+        action,
+        tx_hash: outcome
+            .as_ref()
+            .ok()
+            .and_then(Option::as_ref)
+            .map(|tx| tx.hash),
+        error: outcome.as_ref().err().map(ToString::to_string),
+    };
+    info!(
+        target: "audit",
+        operator = %entry.operator,
+        action = action.name(),
+        tx_hash = ?entry.tx_hash,
+        error = entry.error.as_deref(),
+        "admin action"
+    );
+    let store = state
+        .read()
+        .expect("lock should not be poisoned")
+        .store
+        .clone();
+    match tokio::task::spawn_blocking(move || store.record_audit(&entry)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => error!(?err, "failed to record admin action to the audit log"),
+        Err(err) => error!(?err, "database task failed"),
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum AdminError {
+    #[error("missing or invalid admin credentials")]
+    Unauthorized,
+
+    #[error("wallet is not an operator: {0}")]
+    NotAnOperator(Address),
+
+    #[error("auth error: {0}")]
+    Auth(#[from] AuthError),
+
+    #[error("invalid admin request: {0}")]
+    InvalidRequest(String),
+
+    #[error("listener is not running")]
+    ListenerUnavailable,
+
+    #[error("admin action failed: {0}")]
+    Failed(String),
+}
+
+impl ApiError for AdminError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AdminError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AdminError::NotAnOperator(_) => StatusCode::FORBIDDEN,
+            AdminError::Auth(err) => err.status(),
+            AdminError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            AdminError::ListenerUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            AdminError::Failed(_) => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            AdminError::Unauthorized => "admin_unauthorized",
+            AdminError::NotAnOperator(_) => "not_an_operator",
+            AdminError::Auth(err) => err.code(),
+            AdminError::InvalidRequest(_) => "invalid_admin_request",
+            AdminError::ListenerUnavailable => "listener_unavailable",
+            AdminError::Failed(_) => "admin_action_failed",
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            AdminError::NotAnOperator(address) => Some(json!({ "address": address })),
+            AdminError::Auth(err) => err.details(),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
@@ -0,0 +1,116 @@
+use axum::{
+    Json,
+    extract::{
+        FromRequest, FromRequestParts,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+
+/// Version prefix of the API.
+pub const API_VERSION: &str = "/v1";
+
+/// The body of all error responses.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Stable, machine-readable error code
+    pub code: &'static str,
+
+    /// Human-readable description of the error
+    pub message: String,
+
+    /// Additional information about the error, depending on the code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+/// Errors which can be returned by the API.
+///
+/// The code of an error is part of the API contract and must not change once released.
+pub trait ApiError: std::error::Error {
+    fn status(&self) -> StatusCode;
+
+    fn code(&self) -> &'static str;
+
+    fn details(&self) -> Option<serde_json::Value> {
+        None
+    }
+}
+
+/// Convert an error into a response with the common error body.
+pub fn error_response(err: &impl ApiError) -> Response {
+    let body = ErrorBody {
+        code: err.code(),
+        message: err.to_string(),
+        details: err.details(),
+    };
+    (err.status(), Json(body)).into_response()
+}
+
+/// Errors caused by a malformed request.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum RequestError {
+    #[error("invalid path parameter: {0}")]
+    Path(#[from] PathRejection),
+
+    #[error("invalid query parameter: {0}")]
+    Query(#[from] QueryRejection),
+
+    #[error("invalid JSON body: {0}")]
+    Json(#[from] JsonRejection),
+
+    #[error("route not found")]
+    NotFound,
+}
+
+impl ApiError for RequestError {
+    fn status(&self) -> StatusCode {
+        match self {
+            RequestError::Path(err) => err.status(),
+            RequestError::Query(err) => err.status(),
+            RequestError::Json(err) => err.status(),
+            RequestError::NotFound => StatusCode::NOT_FOUND,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            RequestError::Path(_) => "invalid_path",
+            RequestError::Query(_) => "invalid_query",
+            RequestError::Json(_) => "invalid_body",
+            RequestError::NotFound => "route_not_found",
+        }
+    }
+}
+
+impl IntoResponse for RequestError {
+    fn into_response(self) -> Response {
+        error_response(&self)
+    }
+}
+
+/// Same as [`axum::extract::Path`], with the common error body.
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(RequestError))]
+pub struct Path<T>(pub T);
+
+/// Same as [`axum::extract::Query`], with the common error body.
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(RequestError))]
+pub struct Query<T>(pub T);
+
+/// Same as [`axum::Json`] for request bodies, with the common error body.
+#[derive(Debug, FromRequest)]
+#[from_request(via(axum::Json), rejection(RequestError))]
+pub struct JsonBody<T>(pub T);
+
+#[instrument]
+pub async fn not_found() -> RequestError {
+    info!("unknown route called");
+    RequestError::NotFound
+}
@@ -0,0 +1,875 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use alloy::{
+    hex,
+    primitives::{Address, B256, FixedBytes, PrimitiveSignature, eip191_hash_message, keccak256},
+};
+use axum::{Json, debug_handler, extract::State, http::StatusCode, response::IntoResponse};
+use chrono::Utc;
+use itertools::Itertools as _;
+use rusqlite::{ErrorCode, params};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, error, info, instrument};
+use utoipa::ToSchema;
+
+use crate::{
+    api::{ApiError, ErrorBody, JsonBody, Path, error_response},
+    db::{Store, StoreError},
+    state::AppState,
+};
+
+/// Table of the API keys, identified by the hash of their secret.
+pub(crate) const API_KEYS_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY,
+    key_hash TEXT NOT NULL UNIQUE,
+    proof_hash TEXT NOT NULL UNIQUE,
+    wallet TEXT NOT NULL,
+    scopes TEXT NOT NULL,
+    rate_limit INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER,
+    revoked_at INTEGER
+);
+";
+
+/// Header with which bots authenticate with an API key instead of a bearer token.
+pub const API_KEY_HEADER: &str = "x-pokerd-api-key";
+
+const KEY_PREFIX: &str = "pokerd";
+
+/// Default number of requests per minute allowed with a key.
+pub const DEFAULT_RATE_LIMIT: u32 = 60;
+
+/// Maximum number of requests per minute which can be allowed with a key.
+pub const MAX_RATE_LIMIT: u32 = 600;
+
+/// Maximum number of keys of a wallet which are neither expired nor revoked.
+const MAX_KEYS_PER_WALLET: usize = 10;
+
+/// How long a signed creation or revocation request can be used after it was issued.
+const MAX_PROOF_AGE_SECS: i64 = 5 * 60;
+
+/// Tolerated clock difference with the clients for the issuance time of the requests.
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+/// The endpoints which a key can be used on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    /// `GET /hand`
+    Hand,
+
+    /// `GET /hand/equity`
+    Equity,
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiKeyScope::Hand => write!(f, "hand"),
+            ApiKeyScope::Equity => write!(f, "equity"),
+        }
+    }
+}
+
+/// An API key, without its secret.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiKey {
+    pub id: String,
+
+    /// The wallet which created the key, and which the requests made with it act as
+    #[schema(value_type = String)]
+    pub wallet: Address,
+    pub scopes: Vec<ApiKeyScope>,
+
+    /// Maximum number of requests per minute
+    pub rate_limit: u32,
+
+    /// Unix timestamp
+    pub created_at: i64,
+
+    /// Unix timestamp after which the key is rejected
+    pub expires_at: Option<i64>,
+
+    /// Unix timestamp at which the key was revoked
+    pub revoked_at: Option<i64>,
+}
+
+impl ApiKey {
+    fn is_active(&self, now: i64) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|at| at > now)
+    }
+}
+
+/// An API key as persisted, identified by the hash of its secret.
+#[derive(Debug, Clone)]
+pub struct StoredApiKey {
+    pub key: ApiKey,
+    pub key_hash: B256,
+
+    /// Hash of the signed creation request, so that it can't be replayed
+    pub proof_hash: B256,
+}
+
+#[derive(Debug, Default)]
+struct Keys {
+    by_hash: HashMap<B256, StoredApiKey>,
+
+    /// Start of the current rate limit window, as a Unix minute, and number of requests in it,
+    /// by key ID
+    windows: HashMap<String, (i64, u32)>,
+}
+
+/// The API keys, kept in memory and persisted in the database.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys(Arc<Mutex<Keys>>);
+
+impl ApiKeys {
+    #[must_use]
+    pub fn new(keys: impl IntoIterator<Item = StoredApiKey>) -> Self {
+        Self(Arc::new(Mutex::new(Keys {
+            by_hash: keys.into_iter().map(|key| (key.key_hash, key)).collect(),
+            ..Keys::default()
+        })))
+    }
+
+    /// Check a key and count a request made with it, returning the key.
+    pub fn authenticate(&self, secret: &str) -> Result<ApiKey, ApiKeyError> {
+        let now = Utc::now().timestamp();
+        let mut keys = self.0.lock().expect("api keys lock should not be poisoned");
+        let key = keys
+            .by_hash
+            .get(&keccak256(secret.as_bytes()))
+            .map(|stored| stored.key.clone())
+            .ok_or(ApiKeyError::InvalidKey)?;
+        if key.revoked_at.is_some() {
+            return Err(ApiKeyError::Revoked);
+        }
+        if !key.is_active(now) {
+            return Err(ApiKeyError::Expired);
+        }
+        let minute = now.div_euclid(60);
+        let window = keys.windows.entry(key.id.clone()).or_insert((minute, 0));
+        if window.0 != minute {
+            *window = (minute, 0);
+        }
+        if window.1 >= key.rate_limit {
+            return Err(ApiKeyError::RateLimited {
+                retry_after_secs: 60 - now.rem_euclid(60),
+            });
+        }
+        window.1 += 1;
+        Ok(key)
+    }
+
+    fn check_new(&self, wallet: Address, proof_hash: B256, now: i64) -> Result<(), ApiKeyError> {
+        let keys = self.0.lock().expect("api keys lock should not be poisoned");
+        if keys
+            .by_hash
+            .values()
+            .any(|stored| stored.proof_hash == proof_hash)
+        {
+            return Err(ApiKeyError::ProofReplayed);
+        }
+        let active = keys
+            .by_hash
+            .values()
+            .filter(|stored| stored.key.wallet == wallet && stored.key.is_active(now))
+            .count();
+        if active >= MAX_KEYS_PER_WALLET {
+            return Err(ApiKeyError::TooManyKeys);
+        }
+        Ok(())
+    }
+
+    fn insert(&self, stored: StoredApiKey) {
+        self.0
+            .lock()
+            .expect("api keys lock should not be poisoned")
+            .by_hash
+            .insert(stored.key_hash, stored);
+    }
+
+    fn get(&self, id: &str) -> Option<ApiKey> {
+        self.0
+            .lock()
+            .expect("api keys lock should not be poisoned")
+            .by_hash
+            .values()
+            .find(|stored| stored.key.id == id)
+            .map(|stored| stored.key.clone())
+    }
+
+    fn revoke(&self, id: &str, at: i64) {
+        let mut keys = self.0.lock().expect("api keys lock should not be poisoned");
+        for stored in keys.by_hash.values_mut() {
+            if stored.key.id == id {
+                stored.key.revoked_at = stored.key.revoked_at.or(Some(at));
+            }
+        }
+        keys.windows.remove(id);
+    }
+
+    /// Revoke all the active keys of a wallet, returning their IDs.
+    fn revoke_wallet(&self, wallet: Address, at: i64) -> Vec<String> {
+        let mut keys = self.0.lock().expect("api keys lock should not be poisoned");
+        let mut revoked = vec![];
+        for stored in keys.by_hash.values_mut() {
+            if stored.key.wallet == wallet && stored.key.revoked_at.is_none() {
+                stored.key.revoked_at = Some(at);
+                revoked.push(stored.key.id.clone());
+            }
+        }
+        for id in &revoked {
+            keys.windows.remove(id);
+        }
+        revoked.sort();
+        revoked
+    }
+}
+
+impl Store {
+    /// Record a new API key.
+    pub fn record_api_key(&self, stored: &StoredApiKey) -> Result<(), StoreError> {
+        self.conn().execute(
+            "INSERT INTO api_keys (id, key_hash, proof_hash, wallet, scopes, rate_limit, created_at, expires_at, revoked_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                stored.key.id,
+                stored.key_hash.to_string(),
+                stored.proof_hash.to_string(),
+                stored.key.wallet.to_string(),
+                serde_json::to_string(&stored.key.scopes)?,
+                stored.key.rate_limit,
+                stored.key.created_at,
+                stored.key.expires_at,
+                stored.key.revoked_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Revoke an API key, unless it already was.
+    pub fn revoke_api_key(&self, id: &str, revoked_at: i64) -> Result<(), StoreError> {
+        self.conn().execute(
+            "UPDATE api_keys SET revoked_at = ?2 WHERE id = ?1 AND revoked_at IS NULL",
+            params![id, revoked_at],
+        )?;
+        Ok(())
+    }
+
+    /// Revoke all the API keys of a wallet, except the already revoked ones.
+    pub fn revoke_wallet_api_keys(
+        &self,
+        wallet: Address,
+        revoked_at: i64,
+    ) -> Result<(), StoreError> {
+        self.conn().execute(
+            "UPDATE api_keys SET revoked_at = ?2 WHERE wallet = ?1 AND revoked_at IS NULL",
+            params![wallet.to_string(), revoked_at],
+        )?;
+        Ok(())
+    }
+
+    /// All the API keys, including the expired and revoked ones.
+    pub fn api_keys(&self) -> Result<Vec<StoredApiKey>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, key_hash, proof_hash, wallet, scopes, rate_limit, created_at, expires_at, revoked_at FROM api_keys",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, u32>(5)?,
+                row.get::<_, i64>(6)?,
+                row.get::<_, Option<i64>>(7)?,
+                row.get::<_, Option<i64>>(8)?,
+            ))
+        })?;
+        let mut keys = vec![];
+        for row in rows {
+            let (
+                id,
+                key_hash,
+                proof_hash,
+                wallet,
+                scopes,
+                rate_limit,
+                created_at,
+                expires_at,
+                revoked_at,
+            ) = row?;
+            let invalid = |err: String| StoreError::Invalid(format!("API key {id}: {err}"));
+            keys.push(StoredApiKey {
+                key_hash: key_hash.parse().map_err(|err| invalid(format!("{err}")))?,
+                proof_hash: proof_hash
+                    .parse()
+                    .map_err(|err| invalid(format!("{err}")))?,
+                key: ApiKey {
+                    wallet: wallet.parse().map_err(|err| invalid(format!("{err}")))?,
+                    scopes: serde_json::from_str(&scopes)?,
+                    rate_limit,
+                    created_at,
+                    expires_at,
+                    revoked_at,
+                    id,
+                },
+            });
+        }
+        Ok(keys)
+    }
+}
+
+/// The table which the keys are for, bound in the signed messages so that they can't be replayed
+/// against another table.
+fn table_of(state: &Arc<RwLock<AppState>>) -> Result<(Address, u64), ApiKeyError> {
+    let state = state.read().expect("state lock should not be poisoned");
+    let chain_id = state.listener.chain_id.ok_or(ApiKeyError::ChainUnknown)?;
+    Ok((state.table_address, chain_id))
+}
+
+/// The message which the wallet signs to create a key, with EIP-191.
+fn creation_message(
+    request: &CreateApiKeyRequest,
+    rate_limit: u32,
+    (table, chain_id): (Address, u64),
+) -> String {
+    format!(
+        "pokerd wants to create an API key for {}\nTable: {table}\nChain ID: {chain_id}\nScopes: {}\nRate Limit: {rate_limit}\nExpires At: {}\nIssued At: {}",
+        request.wallet,
+        request.scopes.iter().join(", "),
+        request
+            .expires_at
+            .map_or_else(|| "never".to_string(), |at| at.to_string()),
+        request.issued_at,
+    )
+}
+
+/// The message which the wallet signs to revoke a key, with EIP-191.
+fn revocation_message(
+    id: &str,
+    wallet: Address,
+    issued_at: i64,
+    (table, chain_id): (Address, u64),
+) -> String {
+    format!(
+        "pokerd wants to revoke the API key {id} of {wallet}\nTable: {table}\nChain ID: {chain_id}\nIssued At: {issued_at}"
+    )
+}
+
+/// Map the violation of the uniqueness of the proof, when the same request is sent concurrently,
+/// to the error of [`ApiKeys::check_new`].
+fn replayed_if_duplicate(err: StoreError) -> ApiKeyError {
+    match err {
+        StoreError::Database(rusqlite::Error::SqliteFailure(failure, _))
+            if failure.code == ErrorCode::ConstraintViolation =>
+        {
+            ApiKeyError::ProofReplayed
+        }
+        err => err.into(),
+    }
+}
+
+/// Check that a message was signed by a wallet, either an EOA or a smart contract wallet.
+async fn verify_proof(
+    state: &Arc<RwLock<AppState>>,
+    message: &str,
+    issued_at: i64,
+    wallet: Address,
+    signature: &str,
+) -> Result<(), ApiKeyError> {
+    let now = Utc::now().timestamp();
+    if issued_at > now + MAX_CLOCK_SKEW_SECS {
+        return Err(ApiKeyError::InvalidRequest(
+            "the request is issued in the future".into(),
+        ));
+    }
+    if now - issued_at > MAX_PROOF_AGE_SECS {
+        return Err(ApiKeyError::ProofExpired);
+    }
+    let signature = hex::decode(signature).map_err(|_| ApiKeyError::InvalidSignature)?;
+    let signed_by_eoa = PrimitiveSignature::try_from(signature.as_slice())
+        .ok()
+        .and_then(|sig| sig.recover_address_from_msg(message).ok())
+        .is_some_and(|signer| signer == wallet);
+    if signed_by_eoa {
+        return Ok(());
+    }
+    let contract_signatures = state
+        .read()
+        .expect("state lock should not be poisoned")
+        .contract_signatures
+        .clone();
+    let hash = eip191_hash_message(message);
+    if contract_signatures
+        .is_valid(wallet, hash, signature.into())
+        .await
+    {
+        Ok(())
+    } else {
+        Err(ApiKeyError::InvalidSignature)
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// The wallet creating the key
+    #[schema(value_type = String)]
+    pub wallet: Address,
+
+    /// The endpoints which the key can be used on
+    pub scopes: Vec<ApiKeyScope>,
+
+    /// Maximum number of requests per minute, 60 by default
+    pub rate_limit: Option<u32>,
+
+    /// Unix timestamp after which the key is rejected, never by default
+    pub expires_at: Option<i64>,
+
+    /// Unix timestamp at which the request was signed, at most 5 minutes ago
+    pub issued_at: i64,
+
+    /// EIP-191 signature by the wallet of the message
+    /// `pokerd wants to create an API key for {wallet}\nTable: {table}\nChain ID: {chain_id}\nScopes: {scopes}\nRate Limit: {rate_limit}\nExpires At: {expires_at}\nIssued At: {issued_at}`,
+    /// with the checksummed wallet and table address, the scopes separated by `, ` in the order of
+    /// the request, and `never` if the key doesn't expire
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiKey {
+    /// The secret of the key, to send in the `x-pokerd-api-key` header. It is not shown again
+    pub secret: String,
+    pub key: ApiKey,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api-keys",
+    tag = "auth",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "The key was created", body = CreatedApiKey),
+        (status = 400, description = "Invalid scopes, rate limit or expiry", body = ErrorBody),
+        (status = 401, description = "The signature is not valid or expired, or was already used", body = ErrorBody),
+        (status = 409, description = "The wallet has too many active keys", body = ErrorBody),
+        (status = 500, description = "The key could not be stored", body = ErrorBody),
+        (status = 503, description = "The chain of the table is not known yet", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn create_api_key(
+    State(state): State<Arc<RwLock<AppState>>>,
+    JsonBody(request): JsonBody<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), ApiKeyError> {
+    info!("endpoint called");
+    let now = Utc::now().timestamp();
+    if request.scopes.is_empty() {
+        return Err(ApiKeyError::InvalidRequest("expected a scope".into()));
+    }
+    let rate_limit = request.rate_limit.unwrap_or(DEFAULT_RATE_LIMIT);
+    if rate_limit == 0 || rate_limit > MAX_RATE_LIMIT {
+        return Err(ApiKeyError::InvalidRequest(format!(
+            "the rate limit must be between 1 and {MAX_RATE_LIMIT} requests per minute"
+        )));
+    }
+    if request.expires_at.is_some_and(|at| at <= now) {
+        return Err(ApiKeyError::InvalidRequest(
+            "the key would already be expired".into(),
+        ));
+    }
+    let message = creation_message(&request, rate_limit, table_of(&state)?);
+    verify_proof(
+        &state,
+        &message,
+        request.issued_at,
+        request.wallet,
+        &request.signature,
+    )
+    .await?;
+    let proof_hash = keccak256(message.as_bytes());
+    let (keys, store) = {
+        let state = state.read().expect("state lock should not be poisoned");
+        (state.api_keys.clone(), state.store.clone())
+    };
+    keys.check_new(request.wallet, proof_hash, now)?;
+
+    let id = hex::encode(FixedBytes::<8>::random());
+    let secret = format!("{KEY_PREFIX}_{id}_{}", hex::encode(B256::random()));
+    let stored = StoredApiKey {
+        key: ApiKey {
+            id,
+            wallet: request.wallet,
+            scopes: request.scopes.into_iter().unique().collect(),
+            rate_limit,
+            created_at: now,
+            expires_at: request.expires_at,
+            revoked_at: None,
+        },
+        key_hash: keccak256(secret.as_bytes()),
+        proof_hash,
+    };
+    // the key is only usable once it is persisted, so that it survives a restart
+    tokio::task::spawn_blocking({
+        let stored = stored.clone();
+        move || store.record_api_key(&stored)
+    })
+    .await
+    .map_err(StoreError::from)?
+    .map_err(replayed_if_duplicate)?;
+    keys.insert(stored.clone());
+    info!(id = stored.key.id, wallet = ?stored.key.wallet, "created API key");
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedApiKey {
+            secret,
+            key: stored.key,
+        }),
+    ))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RevokeApiKeyRequest {
+    /// Unix timestamp at which the request was signed, at most 5 minutes ago
+    pub issued_at: i64,
+
+    /// EIP-191 signature by the wallet of the key of the message
+    /// `pokerd wants to revoke the API key {id} of {wallet}\nTable: {table}\nChain ID: {chain_id}\nIssued At: {issued_at}`,
+    /// with the checksummed wallet and table address
+    pub signature: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api-keys/{id}/revoke",
+    tag = "auth",
+    params(("id" = String, Path, description = "ID of the key")),
+    request_body = RevokeApiKeyRequest,
+    responses(
+        (status = 200, description = "The key was revoked, and is not accepted anymore", body = ApiKey),
+        (status = 401, description = "The signature is not valid or expired", body = ErrorBody),
+        (status = 404, description = "The key was not found", body = ErrorBody),
+        (status = 500, description = "The revocation could not be stored", body = ErrorBody),
+        (status = 503, description = "The chain of the table is not known yet", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn revoke_api_key(
+    Path(id): Path<String>,
+    State(state): State<Arc<RwLock<AppState>>>,
+    JsonBody(request): JsonBody<RevokeApiKeyRequest>,
+) -> Result<Json<ApiKey>, ApiKeyError> {
+    info!("endpoint called");
+    let (keys, store) = {
+        let state = state.read().expect("state lock should not be poisoned");
+        (state.api_keys.clone(), state.store.clone())
+    };
+    let key = keys
+        .get(&id)
+        .ok_or_else(|| ApiKeyError::NotFound(id.clone()))?;
+    let message = revocation_message(&id, key.wallet, request.issued_at, table_of(&state)?);
+    verify_proof(
+        &state,
+        &message,
+        request.issued_at,
+        key.wallet,
+        &request.signature,
+    )
+    .await?;
+    let now = Utc::now().timestamp();
+    tokio::task::spawn_blocking({
+        let id = id.clone();
+        move || store.revoke_api_key(&id, now)
+    })
+    .await
+    .map_err(StoreError::from)??;
+    keys.revoke(&id, now);
+    debug!(id, "revoked API key");
+    Ok(Json(keys.get(&id).unwrap_or(key)))
+}
+
+/// Revoke all the API keys of a wallet, e.g. along with its sessions, returning their IDs.
+///
+/// The keys are rejected right away, and only logged if they can't be persisted as revoked.
+pub async fn revoke_wallet_keys(state: &Arc<RwLock<AppState>>, wallet: Address) -> Vec<String> {
+    let (keys, store) = {
+        let state = state.read().expect("state lock should not be poisoned");
+        (state.api_keys.clone(), state.store.clone())
+    };
+    let now = Utc::now().timestamp();
+    let revoked = keys.revoke_wallet(wallet, now);
+    if revoked.is_empty() {
+        return revoked;
+    }
+    match tokio::task::spawn_blocking(move || store.revoke_wallet_api_keys(wallet, now)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => error!(?err, "failed to persist revoked API keys"),
+        Err(err) => error!(?err, "database task failed"),
+    }
+    revoked
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum ApiKeyError {
+    #[error("invalid API key")]
+    InvalidKey,
+
+    #[error("API key expired")]
+    Expired,
+
+    #[error("API key was revoked")]
+    Revoked,
+
+    #[error("API key is not allowed on this endpoint, missing scope {0}")]
+    MissingScope(ApiKeyScope),
+
+    #[error("API key rate limit exceeded")]
+    RateLimited { retry_after_secs: i64 },
+
+    #[error("invalid API key request: {0}")]
+    InvalidRequest(String),
+
+    #[error("invalid signature")]
+    InvalidSignature,
+
+    #[error("signed request expired")]
+    ProofExpired,
+
+    #[error("signed request was already used")]
+    ProofReplayed,
+
+    #[error("too many active API keys for the wallet, at most {MAX_KEYS_PER_WALLET}")]
+    TooManyKeys,
+
+    #[error("API key not found: {0}")]
+    NotFound(String),
+
+    #[error("chain of the table is not known yet")]
+    ChainUnknown,
+
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+impl ApiError for ApiKeyError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiKeyError::InvalidKey
+            | ApiKeyError::Expired
+            | ApiKeyError::Revoked
+            | ApiKeyError::InvalidSignature
+            | ApiKeyError::ProofExpired
+            | ApiKeyError::ProofReplayed => StatusCode::UNAUTHORIZED,
+            ApiKeyError::MissingScope(_) => StatusCode::FORBIDDEN,
+            ApiKeyError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiKeyError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            ApiKeyError::TooManyKeys => StatusCode::CONFLICT,
+            ApiKeyError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiKeyError::ChainUnknown => StatusCode::SERVICE_UNAVAILABLE,
+            ApiKeyError::Store(err) => err.status(),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            ApiKeyError::InvalidKey => "invalid_api_key",
+            ApiKeyError::Expired => "api_key_expired",
+            ApiKeyError::Revoked => "api_key_revoked",
+            ApiKeyError::MissingScope(_) => "api_key_scope_missing",
+            ApiKeyError::RateLimited { .. } => "api_key_rate_limited",
+            ApiKeyError::InvalidRequest(_) => "invalid_api_key_request",
+            ApiKeyError::InvalidSignature => "invalid_signature",
+            ApiKeyError::ProofExpired => "signed_request_expired",
+            ApiKeyError::ProofReplayed => "signed_request_replayed",
+            ApiKeyError::TooManyKeys => "too_many_api_keys",
+            ApiKeyError::NotFound(_) => "api_key_not_found",
+            ApiKeyError::ChainUnknown => "chain_unknown",
+            ApiKeyError::Store(err) => err.code(),
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            ApiKeyError::MissingScope(scope) => Some(json!({ "scope": scope })),
+            ApiKeyError::RateLimited { retry_after_secs } => {
+                Some(json!({ "retry_after_secs": retry_after_secs }))
+            }
+            ApiKeyError::NotFound(id) => Some(json!({ "id": id })),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for ApiKeyError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(id: &str, key_hash: B256, proof_hash: B256) -> StoredApiKey {
+        StoredApiKey {
+            key: ApiKey {
+                id: id.to_string(),
+                wallet: Address::repeat_byte(1),
+                scopes: vec![ApiKeyScope::Hand],
+                rate_limit: DEFAULT_RATE_LIMIT,
+                created_at: 0,
+                expires_at: None,
+                revoked_at: None,
+            },
+            key_hash,
+            proof_hash,
+        }
+    }
+
+    #[test]
+    fn rate_limit_per_minute() {
+        let mut key = stored("a", keccak256("secret"), B256::ZERO);
+        key.key.rate_limit = 2;
+        let keys = ApiKeys::new([key]);
+        assert!(keys.authenticate("secret").is_ok());
+        assert!(keys.authenticate("secret").is_ok());
+        assert!(matches!(
+            keys.authenticate("secret"),
+            Err(ApiKeyError::RateLimited {
+                retry_after_secs: 1..=60
+            })
+        ));
+
+        // a new window starts the next minute
+        keys.0.lock().unwrap().windows.get_mut("a").unwrap().0 -= 1;
+        assert!(keys.authenticate("secret").is_ok());
+        assert_eq!(keys.0.lock().unwrap().windows["a"].1, 1);
+    }
+
+    #[test]
+    fn expired_key() {
+        let now = Utc::now().timestamp();
+        let mut expired = stored("a", keccak256("expired"), B256::repeat_byte(1));
+        expired.key.expires_at = Some(now - 1);
+        let mut valid = stored("b", keccak256("valid"), B256::repeat_byte(2));
+        valid.key.expires_at = Some(now + 3600);
+        let keys = ApiKeys::new([expired, valid]);
+        assert!(matches!(
+            keys.authenticate("expired"),
+            Err(ApiKeyError::Expired)
+        ));
+        assert_eq!(keys.authenticate("valid").unwrap().id, "b");
+        assert!(matches!(
+            keys.authenticate("unknown"),
+            Err(ApiKeyError::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn revoked_key() {
+        let keys = ApiKeys::new([stored("a", keccak256("secret"), B256::ZERO)]);
+        assert!(keys.authenticate("secret").is_ok());
+        keys.revoke("a", Utc::now().timestamp());
+        assert!(matches!(
+            keys.authenticate("secret"),
+            Err(ApiKeyError::Revoked)
+        ));
+        assert!(!keys.0.lock().unwrap().windows.contains_key("a"));
+    }
+
+    #[test]
+    fn too_many_keys() {
+        let now = Utc::now().timestamp();
+        let wallet = Address::repeat_byte(1);
+        let active = (0..MAX_KEYS_PER_WALLET).map(|i| {
+            let i = u8::try_from(i).unwrap();
+            stored(
+                &i.to_string(),
+                B256::with_last_byte(i),
+                B256::with_last_byte(i),
+            )
+        });
+        let mut expired = stored("expired", B256::repeat_byte(0xe0), B256::repeat_byte(0xe0));
+        expired.key.expires_at = Some(now - 1);
+        let mut revoked = stored("revoked", B256::repeat_byte(0xe1), B256::repeat_byte(0xe1));
+        revoked.key.revoked_at = Some(now - 1);
+        let keys = ApiKeys::new(active.chain([expired, revoked]));
+        assert!(matches!(
+            keys.check_new(wallet, B256::repeat_byte(0xf0), now),
+            Err(ApiKeyError::TooManyKeys)
+        ));
+        assert!(
+            keys.check_new(Address::repeat_byte(2), B256::repeat_byte(0xf0), now)
+                .is_ok()
+        );
+
+        // the proof of an existing key can't be used for another one
+        assert!(matches!(
+            keys.check_new(Address::repeat_byte(2), B256::with_last_byte(0), now),
+            Err(ApiKeyError::ProofReplayed)
+        ));
+
+        keys.revoke("0", now);
+        assert!(keys.check_new(wallet, B256::repeat_byte(0xf0), now).is_ok());
+    }
+
+    #[test]
+    fn duplicate_proof_is_replayed() {
+        let store = Store::open(":memory:").unwrap();
+        let proof_hash = B256::repeat_byte(2);
+        store
+            .record_api_key(&stored("a", B256::repeat_byte(1), proof_hash))
+            .unwrap();
+        let err = store
+            .record_api_key(&stored("b", B256::repeat_byte(3), proof_hash))
+            .unwrap_err();
+        assert!(matches!(
+            replayed_if_duplicate(err),
+            ApiKeyError::ProofReplayed
+        ));
+        assert_eq!(store.api_keys().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn messages_are_bound_to_the_table() {
+        let request = CreateApiKeyRequest {
+            wallet: Address::repeat_byte(1),
+            scopes: vec![ApiKeyScope::Hand, ApiKeyScope::Equity],
+            rate_limit: None,
+            expires_at: None,
+            issued_at: 1_700_000_000,
+            signature: String::new(),
+        };
+        let table = Address::repeat_byte(0xaa);
+        let message = creation_message(&request, DEFAULT_RATE_LIMIT, (table, 8453));
+        assert_eq!(
+            message,
+            format!(
+                "pokerd wants to create an API key for {}\nTable: {table}\nChain ID: 8453\n\
+                 Scopes: hand, equity\nRate Limit: {DEFAULT_RATE_LIMIT}\nExpires At: never\n\
+                 Issued At: 1700000000",
+                request.wallet
+            )
+        );
+        assert_ne!(
+            message,
+            creation_message(&request, DEFAULT_RATE_LIMIT, (table, 1))
+        );
+        assert_ne!(
+            revocation_message("id", request.wallet, 0, (table, 8453)),
+            revocation_message("id", request.wallet, 0, (Address::ZERO, 8453))
+        );
+    }
+}
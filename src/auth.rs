@@ -0,0 +1,444 @@
+use std::{
+    collections::HashMap,
+    env, fmt,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use alloy::{
+    hex,
+    primitives::{Address, B256},
+};
+use anyhow::{Context as _, Result, bail};
+use axum::{
+    RequestPartsExt as _,
+    extract::FromRequestParts,
+    http::{HeaderMap, StatusCode, request::Parts},
+    response::IntoResponse,
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, warn};
+
+use crate::{
+    api::{ApiError, error_response},
+    policy::{PolicyError, UserAttributes},
+    privy::{self, Privy, PrivyConfig, PrivyError},
+    state::AppState,
+};
+
+/// Issuer of the tokens minted by [`DevAuth`].
+const DEV_ISSUER: &str = "pokerd-dev";
+
+/// Default validity of the tokens minted by [`DevAuth`].
+pub const DEFAULT_DEV_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Validates the bearer tokens of the players and resolves their wallet.
+pub trait AuthProvider: fmt::Debug + Send + Sync {
+    fn authenticate<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<UserSession, AuthError>>;
+}
+
+/// Build the provider selected by the `AUTH_PROVIDER` environment variable, `privy` by default.
+pub fn provider_from_env() -> Result<Arc<dyn AuthProvider>> {
+    match env::var("AUTH_PROVIDER").as_deref().unwrap_or("privy") {
+        "privy" => {
+            let privy = Privy::new(PrivyConfig::from_env()?)?;
+            tokio::spawn(privy::run_cache_sweep(privy.clone()));
+            Ok(Arc::new(privy))
+        }
+        "dev" => {
+            warn!("using the dev auth provider, tokens are not checked with Privy");
+            Ok(Arc::new(DevAuth::from_env()?))
+        }
+        other => bail!("unknown AUTH_PROVIDER {other}, expected privy or dev"),
+    }
+}
+
+/// Header with which clients select which of their linked wallets to act as.
+pub const WALLET_HEADER: &str = "x-pokerd-wallet";
+
+#[derive(Debug, Clone)]
+pub struct UserSession {
+    pub user_id: String,
+    pub session_id: String,
+
+    /// All the EVM wallets linked to the user, never empty
+    pub wallets: Vec<Address>,
+
+    /// The wallet selected with the `x-pokerd-wallet` header, checked to be linked to the user
+    pub selected: Option<Address>,
+
+    /// The attributes of the user, when reported by the provider
+    pub attributes: Option<UserAttributes>,
+
+    /// The Privy IDs of the wallets which the user delegated to the backend
+    pub delegated: HashMap<Address, String>,
+}
+
+impl UserSession {
+    /// A session with at least one wallet, the first one being the primary wallet of the user.
+    #[must_use]
+    pub fn new(user_id: String, session_id: String, wallets: Vec<Address>) -> Self {
+        debug_assert!(!wallets.is_empty(), "a session should have a wallet");
+        Self {
+            user_id,
+            session_id,
+            wallets,
+            selected: None,
+            attributes: None,
+            delegated: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_attributes(mut self, attributes: UserAttributes) -> Self {
+        self.attributes = Some(attributes);
+        self
+    }
+
+    #[must_use]
+    pub fn with_delegated(mut self, delegated: HashMap<Address, String>) -> Self {
+        self.delegated = delegated;
+        self
+    }
+
+    /// The selected wallet, or else the first linked wallet matching a predicate (e.g. being seated
+    /// at the table), or else the primary one.
+    pub fn wallet_for(&self, matches: impl Fn(Address) -> bool) -> Address {
+        self.selected
+            .or_else(|| self.wallets.iter().copied().find(|wallet| matches(*wallet)))
+            .or_else(|| self.wallets.first().copied())
+            .unwrap_or_default()
+    }
+
+    fn select(&mut self, headers: &HeaderMap) -> Result<(), AuthError> {
+        let Some(selector) = headers.get(WALLET_HEADER) else {
+            return Ok(());
+        };
+        let wallet: Address = selector
+            .to_str()
+            .ok()
+            .and_then(|selector| selector.parse().ok())
+            .ok_or(AuthError::InvalidWalletSelector)?;
+        if !self.wallets.contains(&wallet) {
+            return Err(AuthError::WalletNotLinked(wallet));
+        }
+        self.selected = Some(wallet);
+        Ok(())
+    }
+}
+
+impl FromRequestParts<Arc<RwLock<AppState>>> for UserSession {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<RwLock<AppState>>,
+    ) -> Result<Self, Self::Rejection> {
+        // Extract the token from the authorization header
+        let TypedHeader(Authorization(bearer)) = parts
+            .extract::<TypedHeader<Authorization<Bearer>>>()
+            .await
+            .map_err(|_| AuthError::InvalidToken)?;
+        // validate token
+        let auth = Arc::clone(&state.read().expect("lock should not be poisoned").auth);
+        let mut session = auth.authenticate(bearer.token()).await?;
+        session.select(&parts.headers)?;
+        {
+            let state = state.read().expect("lock should not be poisoned");
+            state.policy.check(
+                session.attributes.as_ref(),
+                state.stake_of(&session.wallets),
+            )?;
+            state.sessions.touch(&session, |wallet| {
+                state.table_players.iter().any(|p| p.address == wallet)
+            })?;
+        }
+        Ok(session)
+    }
+}
+
+/// Local provider for development and tests, which doesn't need Privy credentials.
+///
+/// Accepts the tokens signed with `DEV_AUTH_SECRET`, see [`DevAuth::mint`], and the static tokens
+/// listed in `DEV_AUTH_TOKENS` as comma-separated `token=wallet` pairs.
+#[derive(Clone)]
+pub struct DevAuth {
+    secret: Option<String>,
+    tokens: HashMap<String, Address>,
+}
+
+impl fmt::Debug for DevAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DevAuth")
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("wallets", &self.tokens.values().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Claims of the tokens minted by [`DevAuth`].
+#[derive(Debug, Serialize, Deserialize)]
+struct DevClaims {
+    #[serde(rename = "iss")]
+    issuer: String,
+    #[serde(rename = "sub")]
+    user_id: String,
+    #[serde(rename = "sid")]
+    session_id: String,
+    #[serde(rename = "exp")]
+    expiration: i64,
+    wallet: Address,
+}
+
+impl DevAuth {
+    pub fn from_env() -> Result<Self> {
+        let secret = env::var("DEV_AUTH_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        let tokens = env::var("DEV_AUTH_TOKENS")
+            .ok()
+            .map(|tokens| {
+                tokens
+                    .split(',')
+                    .filter(|pair| !pair.trim().is_empty())
+                    .map(|pair| {
+                        let (token, wallet) = pair
+                            .split_once('=')
+                            .context("expected token=wallet pairs")?;
+                        Ok((token.trim().to_string(), wallet.trim().parse()?))
+                    })
+                    .collect::<Result<HashMap<_, _>>>()
+            })
+            .transpose()
+            .context("DEV_AUTH_TOKENS environment variable")?
+            .unwrap_or_default();
+        if secret.is_none() && tokens.is_empty() {
+            bail!("the dev auth provider needs DEV_AUTH_SECRET or DEV_AUTH_TOKENS");
+        }
+        Ok(Self { secret, tokens })
+    }
+
+    /// Sign a token for a wallet, to use as the bearer token of the API in development and tests.
+    pub fn mint(&self, wallet: Address, ttl: Duration) -> Result<String> {
+        let secret = self
+            .secret
+            .as_ref()
+            .context("DEV_AUTH_SECRET is needed to mint tokens")?;
+        let now = Utc::now().timestamp();
+        let claims = DevClaims {
+            issuer: DEV_ISSUER.to_string(),
+            user_id: format!("dev:{wallet}"),
+            session_id: format!("dev:{}", hex::encode(B256::random())),
+            expiration: now + i64::try_from(ttl.as_secs())?,
+            wallet,
+        };
+        Ok(encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )?)
+    }
+
+    fn validate(&self, token: &str) -> Result<UserSession, AuthError> {
+        if let Some(wallet) = self.tokens.get(token) {
+            return Ok(UserSession::new(
+                format!("dev:{wallet}"),
+                format!("static:{wallet}"),
+                vec![*wallet],
+            ));
+        }
+        let secret = self.secret.as_ref().ok_or(AuthError::InvalidToken)?;
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[DEV_ISSUER]);
+        let claims = decode::<DevClaims>(
+            token,
+            &DecodingKey::from_secret(secret.as_bytes()),
+            &validation,
+        )
+        .map_err(AuthError::InvalidDevToken)?
+        .claims;
+        debug!(?claims, "dev token validated");
+        Ok(UserSession::new(
+            claims.user_id,
+            claims.session_id,
+            vec![claims.wallet],
+        ))
+    }
+}
+
+#[cfg(test)]
+impl DevAuth {
+    /// A provider minting and accepting tokens signed with a fixed secret.
+    pub fn for_tests() -> Self {
+        Self {
+            secret: Some("pokerd test secret".to_string()),
+            tokens: HashMap::new(),
+        }
+    }
+
+    /// Mint a token for a wallet, valid for an hour.
+    pub fn test_token(&self, wallet: Address) -> String {
+        self.mint(wallet, Duration::from_secs(60 * 60))
+            .expect("test token should be minted")
+    }
+}
+
+impl AuthProvider for DevAuth {
+    fn authenticate<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<UserSession, AuthError>> {
+        Box::pin(async move { self.validate(token) })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("invalid or missing token")]
+    InvalidToken,
+
+    #[error("failed to validate dev token: {0}")]
+    InvalidDevToken(jsonwebtoken::errors::Error),
+
+    #[error("failed to validate session token: {0}")]
+    InvalidSession(jsonwebtoken::errors::Error),
+
+    #[error("session was revoked")]
+    SessionRevoked,
+
+    #[error("wallet is used by a newer session: {0}")]
+    SessionSuperseded(Address),
+
+    #[error("invalid wallet selector")]
+    InvalidWalletSelector,
+
+    #[error("wallet is not linked to the user: {0}")]
+    WalletNotLinked(Address),
+
+    #[error("user is not eligible: {0}")]
+    Policy(#[from] PolicyError),
+
+    #[error(transparent)]
+    Privy(#[from] PrivyError),
+}
+
+impl ApiError for AuthError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AuthError::InvalidToken
+            | AuthError::InvalidDevToken(_)
+            | AuthError::InvalidSession(_) => StatusCode::UNAUTHORIZED,
+            AuthError::SessionRevoked | AuthError::SessionSuperseded(_) => StatusCode::UNAUTHORIZED,
+            AuthError::InvalidWalletSelector => StatusCode::BAD_REQUEST,
+            AuthError::WalletNotLinked(_) => StatusCode::FORBIDDEN,
+            AuthError::Policy(err) => err.status(),
+            AuthError::Privy(err) => err.status(),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            AuthError::InvalidToken
+            | AuthError::InvalidDevToken(_)
+            | AuthError::InvalidSession(_) => "invalid_token",
+            AuthError::SessionRevoked => "session_revoked",
+            AuthError::SessionSuperseded(_) => "session_superseded",
+            AuthError::InvalidWalletSelector => "invalid_wallet_selector",
+            AuthError::WalletNotLinked(_) => "wallet_not_linked",
+            AuthError::Policy(err) => err.code(),
+            AuthError::Privy(err) => err.code(),
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            AuthError::SessionSuperseded(wallet) | AuthError::WalletNotLinked(wallet) => {
+                Some(json!({ "wallet": wallet }))
+            }
+            AuthError::Policy(err) => err.details(),
+            AuthError::Privy(err) => err.details(),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mint_then_authenticate() {
+        let auth = DevAuth::for_tests();
+        let wallet = Address::repeat_byte(1);
+        let session = auth.authenticate(&auth.test_token(wallet)).await.unwrap();
+        assert_eq!(session.wallets, vec![wallet]);
+        assert_eq!(session.user_id, format!("dev:{wallet}"));
+        assert!(session.session_id.starts_with("dev:"));
+    }
+
+    #[tokio::test]
+    async fn tokens_have_distinct_sessions() {
+        let auth = DevAuth::for_tests();
+        let wallet = Address::repeat_byte(1);
+        let first = auth.authenticate(&auth.test_token(wallet)).await.unwrap();
+        let second = auth.authenticate(&auth.test_token(wallet)).await.unwrap();
+        assert_ne!(first.session_id, second.session_id);
+    }
+
+    #[tokio::test]
+    async fn reject_other_secret_and_expired_tokens() {
+        let auth = DevAuth::for_tests();
+        let other = DevAuth {
+            secret: Some("another secret".to_string()),
+            tokens: HashMap::new(),
+        };
+        let wallet = Address::repeat_byte(1);
+        assert!(matches!(
+            auth.authenticate(&other.test_token(wallet)).await,
+            Err(AuthError::InvalidDevToken(_))
+        ));
+        // beyond the default leeway of a minute
+        let expired = encode(
+            &Header::new(Algorithm::HS256),
+            &DevClaims {
+                issuer: DEV_ISSUER.to_string(),
+                user_id: format!("dev:{wallet}"),
+                session_id: "dev:expired".to_string(),
+                expiration: Utc::now().timestamp() - 120,
+                wallet,
+            },
+            &EncodingKey::from_secret(b"pokerd test secret"),
+        )
+        .unwrap();
+        assert!(matches!(
+            auth.authenticate(&expired).await,
+            Err(AuthError::InvalidDevToken(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn static_tokens() {
+        let wallet = Address::repeat_byte(2);
+        let auth = DevAuth {
+            secret: None,
+            tokens: HashMap::from([("alice".to_string(), wallet)]),
+        };
+        let session = auth.authenticate("alice").await.unwrap();
+        assert_eq!(session.wallets, vec![wallet]);
+        assert!(matches!(
+            auth.authenticate("bob").await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+}
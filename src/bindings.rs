@@ -60,5 +60,16 @@ sol! {
         function revealShowdownResult(string[] calldata cards, uint256[] calldata winners) external;
         function timeoutCurrentPlayer() external;
         function cancelCurrentRound() external;
+        function bet(uint256 amount) external;
+        function fold() external;
+        function leaveTable() external;
+    }
+}
+
+sol! {
+    /// Signature validation of smart contract wallets (EIP-1271).
+    #[sol(rpc)]
+    interface IERC1271 {
+        function isValidSignature(bytes32 hash, bytes signature) external view returns (bytes4 magicValue);
     }
 }
@@ -2,65 +2,208 @@ use std::sync::{Arc, RwLock};
 
 use alloy::primitives::Address;
 use axum::{Json, debug_handler, extract::State, http::StatusCode, response::IntoResponse};
-use rs_poker::core::{Card, Hand};
+use serde::Deserialize;
 use serde_json::json;
 use tracing::{info, instrument};
+use utoipa::IntoParams;
 
-use crate::{privy::UserSession, state::AppState};
+use crate::{
+    api::{ApiError, ErrorBody, Query, error_response},
+    api_keys::{ApiKeyError, ApiKeyScope},
+    codec::{self, FormatQuery},
+    equity::{self, DEFAULT_ITERATIONS, Equity, MAX_ITERATIONS},
+    openapi::{CardSchema, HandSchema},
+    sealed::SealedError,
+    signed_request::Requester,
+    state::AppState,
+};
 
+#[utoipa::path(
+    get,
+    path = "/hand",
+    tag = "cards",
+    params(
+        FormatQuery,
+        ("x-pokerd-round-id" = Option<String>, Header, description = "ID of the current round, for a signed request"),
+        ("x-pokerd-seat" = Option<u64>, Header, description = "Seat of the player, for a signed request"),
+        ("x-pokerd-expiry" = Option<u64>, Header, description = "Unix timestamp at which the signed request expires, at most 5 minutes ahead"),
+        ("x-pokerd-signature" = Option<String>, Header, description = "EIP-712 signature of the `HandRequest(address table,uint256 roundId,uint256 seat,uint64 expiry)` by the player's wallet, in the `pokerd` domain of the table. Replaces the bearer token"),
+    ),
+    security((), ("bearer" = []), ("api_key" = [])),
+    responses(
+        (status = 200, description = "The hole cards of the authenticated player, as `SealedCards` encrypted to the hand key if the session registered one", body = HandSchema),
+        (status = 400, description = "Invalid signed request headers", body = ErrorBody),
+        (status = 401, description = "Missing or invalid access token, signature or API key", body = ErrorBody),
+        (status = 403, description = "The user is not eligible to play at the table, or the API key lacks the scope of the endpoint", body = ErrorBody),
+        (status = 404, description = "The player is not seated in the current round", body = ErrorBody),
+        (status = 409, description = "The round has not started yet, or the signed request is for another round", body = ErrorBody),
+        (status = 429, description = "The rate limit of the API key is exceeded", body = ErrorBody),
+        (status = 502, description = "The authentication provider failed", body = ErrorBody),
+    )
+)]
 #[debug_handler]
 #[instrument]
 pub async fn hand(
-    session: UserSession,
+    requester: Requester,
+    Query(query): Query<FormatQuery>,
     State(state): State<Arc<RwLock<AppState>>>,
-) -> Result<Json<Hand>, CardsError> {
+) -> Result<Json<serde_json::Value>, CardsError> {
     info!("endpoint called");
+    requester.require(ApiKeyScope::Hand)?;
     let state = state.read().expect("state lock should not be poisoned");
     let Some(players) = state.get_players() else {
         return Err(CardsError::GameNotStarted);
     };
-    let Some(player) = players.iter().find(|p| p.address == session.wallet) else {
-        return Err(CardsError::PlayerNotFound(session.wallet));
+    let wallet = requester.wallet_for(|wallet| players.iter().any(|p| p.address == wallet));
+    let Some(player) = players.iter().find(|p| p.address == wallet) else {
+        return Err(CardsError::PlayerNotFound(wallet));
+    };
+    let cards = player.starting_hand.clone();
+    let key = match &requester {
+        Requester::Session(session) => state.sessions.hand_key(&session.session_id),
+        Requester::Signed(_) | Requester::ApiKey(_) => None,
+    };
+    drop(state);
+    match key {
+        Some(key) => Ok(Json(json!(key.seal_cards(cards.iter(), query.format)?))),
+        None => Ok(Json(codec::encode_cards(cards.iter(), query.format))),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct EquityQuery {
+    /// Number of simulated deals
+    pub iterations: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/hand/equity",
+    tag = "cards",
+    params(
+        EquityQuery,
+        ("x-pokerd-round-id" = Option<String>, Header, description = "ID of the current round, for a signed request"),
+        ("x-pokerd-seat" = Option<u64>, Header, description = "Seat of the player, for a signed request"),
+        ("x-pokerd-expiry" = Option<u64>, Header, description = "Unix timestamp at which the signed request expires, at most 5 minutes ahead"),
+        ("x-pokerd-signature" = Option<String>, Header, description = "EIP-712 signature of a `HandRequest` by the player's wallet, as for `GET /hand`. Replaces the bearer token"),
+    ),
+    security((), ("bearer" = []), ("api_key" = [])),
+    responses(
+        (status = 200, description = "The equity of the authenticated player's hand", body = Equity),
+        (status = 400, description = "Invalid signed request headers", body = ErrorBody),
+        (status = 401, description = "Missing or invalid access token, signature or API key", body = ErrorBody),
+        (status = 403, description = "The user is not eligible to play at the table, or the API key lacks the scope of the endpoint", body = ErrorBody),
+        (status = 404, description = "The player is not seated in the current round", body = ErrorBody),
+        (status = 409, description = "The round has not started yet, or the signed request is for another round", body = ErrorBody),
+        (status = 429, description = "The rate limit of the API key is exceeded", body = ErrorBody),
+        (status = 502, description = "The authentication provider failed", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn hand_equity(
+    requester: Requester,
+    Query(query): Query<EquityQuery>,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<Equity>, CardsError> {
+    info!("endpoint called");
+    requester.require(ApiKeyScope::Equity)?;
+    let state = state.read().expect("state lock should not be poisoned");
+    let Some(players) = state.get_players() else {
+        return Err(CardsError::GameNotStarted);
+    };
+    let wallet = requester.wallet_for(|wallet| players.iter().any(|p| p.address == wallet));
+    let Some(player) = players.iter().find(|p| p.address == wallet) else {
+        return Err(CardsError::PlayerNotFound(wallet));
     };
     let hand = player.starting_hand.clone();
+    let opponents = players.len() - 1;
+    let board = state.get_board();
     drop(state);
-    Ok(Json(hand))
+    let iterations = query
+        .iterations
+        .unwrap_or(DEFAULT_ITERATIONS)
+        .min(MAX_ITERATIONS);
+    let equity =
+        tokio::task::spawn_blocking(move || equity::simulate(&hand, &board, opponents, iterations))
+            .await
+            .expect("equity simulation should not panic");
+    Ok(Json(equity))
 }
 
+#[utoipa::path(
+    get,
+    path = "/flop",
+    tag = "cards",
+    params(FormatQuery),
+    responses(
+        (status = 200, description = "The flop", body = HandSchema),
+        (status = 409, description = "The flop was not revealed yet", body = ErrorBody),
+    )
+)]
 #[debug_handler]
 #[instrument]
-pub async fn flop(State(state): State<Arc<RwLock<AppState>>>) -> Result<Json<Hand>, CardsError> {
+pub async fn flop(
+    Query(query): Query<FormatQuery>,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<serde_json::Value>, CardsError> {
     info!("endpoint called");
     let state = state.read().expect("state lock should not be poisoned");
     let Some(flop) = state.get_flop() else {
         return Err(CardsError::FlopNotAvailable);
     };
     drop(state);
-    Ok(Json(flop))
+    Ok(Json(codec::encode_cards(flop.iter(), query.format)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/turn",
+    tag = "cards",
+    params(FormatQuery),
+    responses(
+        (status = 200, description = "The turn card", body = CardSchema),
+        (status = 409, description = "The turn card was not revealed yet", body = ErrorBody),
+    )
+)]
 #[debug_handler]
 #[instrument]
-pub async fn turn(State(state): State<Arc<RwLock<AppState>>>) -> Result<Json<Card>, CardsError> {
+pub async fn turn(
+    Query(query): Query<FormatQuery>,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<serde_json::Value>, CardsError> {
     info!("endpoint called");
     let state = state.read().expect("state lock should not be poisoned");
     let Some(turn) = state.get_turn() else {
         return Err(CardsError::TurnNotAvailable);
     };
     drop(state);
-    Ok(Json(turn))
+    Ok(Json(codec::encode_card(turn, query.format)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/river",
+    tag = "cards",
+    params(FormatQuery),
+    responses(
+        (status = 200, description = "The river card", body = CardSchema),
+        (status = 409, description = "The river card was not revealed yet", body = ErrorBody),
+    )
+)]
 #[debug_handler]
 #[instrument]
-pub async fn river(State(state): State<Arc<RwLock<AppState>>>) -> Result<Json<Card>, CardsError> {
+pub async fn river(
+    Query(query): Query<FormatQuery>,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<serde_json::Value>, CardsError> {
     info!("endpoint called");
     let state = state.read().expect("state lock should not be poisoned");
     let Some(river) = state.get_river() else {
         return Err(CardsError::RiverNotAvailable);
     };
     drop(state);
-    Ok(Json(river))
+    Ok(Json(codec::encode_card(river, query.format)))
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -80,14 +223,54 @@ pub enum CardsError {
 
     #[error("player not found: {0}")]
     PlayerNotFound(Address),
+
+    #[error(transparent)]
+    ApiKey(#[from] ApiKeyError),
+
+    #[error(transparent)]
+    Sealed(#[from] SealedError),
+}
+
+impl ApiError for CardsError {
+    fn status(&self) -> StatusCode {
+        match self {
+            CardsError::GameNotStarted
+            | CardsError::FlopNotAvailable
+            | CardsError::TurnNotAvailable
+            | CardsError::RiverNotAvailable => StatusCode::CONFLICT,
+            CardsError::PlayerNotFound(_) => StatusCode::NOT_FOUND,
+            CardsError::ApiKey(err) => err.status(),
+            CardsError::Sealed(err) => err.status(),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            CardsError::GameNotStarted => "game_not_started",
+            CardsError::FlopNotAvailable
+            | CardsError::TurnNotAvailable
+            | CardsError::RiverNotAvailable => "card_not_revealed",
+            CardsError::PlayerNotFound(_) => "player_not_seated",
+            CardsError::ApiKey(err) => err.code(),
+            CardsError::Sealed(err) => err.code(),
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            CardsError::GameNotStarted => None,
+            CardsError::FlopNotAvailable => Some(json!({ "card": "flop" })),
+            CardsError::TurnNotAvailable => Some(json!({ "card": "turn" })),
+            CardsError::RiverNotAvailable => Some(json!({ "card": "river" })),
+            CardsError::PlayerNotFound(address) => Some(json!({ "address": address })),
+            CardsError::ApiKey(err) => err.details(),
+            CardsError::Sealed(err) => err.details(),
+        }
+    }
 }
 
 impl IntoResponse for CardsError {
     fn into_response(self) -> axum::response::Response {
-        let status = StatusCode::BAD_REQUEST; // for now all errors map to this error code, use a match if this changes
-        let body = Json(json!({
-            "error": self.to_string(),
-        }));
-        (status, body).into_response()
+        error_response(&self)
     }
 }
@@ -0,0 +1,441 @@
+use rs_poker::core::{Card, Suit, Value};
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
+
+const VALUES: [Value; 13] = [
+    Value::Two,
+    Value::Three,
+    Value::Four,
+    Value::Five,
+    Value::Six,
+    Value::Seven,
+    Value::Eight,
+    Value::Nine,
+    Value::Ten,
+    Value::Jack,
+    Value::Queen,
+    Value::King,
+    Value::Ace,
+];
+
+const SUITS: [Suit; 4] = [Suit::Spade, Suit::Club, Suit::Heart, Suit::Diamond];
+
+/// Number of cards in a deck.
+pub const DECK_SIZE: u8 = 52;
+
+/// Notation used to encode cards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CardFormat {
+    /// Serde representation of `rs_poker`, e.g. `{"value":"Ace","suit":"Spade"}`
+    #[default]
+    Json,
+
+    /// Value and suit characters, as used on-chain, e.g. `As`
+    Compact,
+
+    /// Index in the deck from 0 to 51, suits first: spades, clubs, hearts then diamonds
+    Index,
+
+    /// Unicode playing card glyph, e.g. `🂡`
+    Unicode,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct FormatQuery {
+    /// Notation of the returned cards
+    #[serde(default)]
+    pub format: CardFormat,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CodecError {
+    #[error("invalid card: {0:?}")]
+    InvalidCard(String),
+
+    #[error("invalid card index: {0}")]
+    InvalidIndex(u8),
+
+    #[error("invalid card value: {0:?}")]
+    InvalidValue(char),
+
+    #[error("invalid card suit: {0:?}")]
+    InvalidSuit(char),
+
+    #[error("missing suit after value {0:?}")]
+    MissingSuit(char),
+
+    #[error("duplicate card: {0}")]
+    DuplicateCard(String),
+}
+
+/// Encode a single card in the given notation.
+#[must_use]
+pub fn encode_card(card: Card, format: CardFormat) -> serde_json::Value {
+    match format {
+        CardFormat::Json => serde_json::to_value(card).expect("card should be serializable"),
+        CardFormat::Compact => json!(card_to_string(card)),
+        CardFormat::Index => json!(card_to_index(card)),
+        CardFormat::Unicode => json!(card_to_unicode(card).to_string()),
+    }
+}
+
+/// Encode a set of cards in the given notation.
+///
+/// Compact and Unicode cards are concatenated into a single string, the other notations give an array.
+#[must_use]
+pub fn encode_cards(
+    cards: impl IntoIterator<Item = Card>,
+    format: CardFormat,
+) -> serde_json::Value {
+    let cards = cards.into_iter();
+    match format {
+        CardFormat::Json | CardFormat::Index => cards.map(|c| encode_card(c, format)).collect(),
+        CardFormat::Compact => json!(cards.map(card_to_string).collect::<String>()),
+        CardFormat::Unicode => json!(cards.map(card_to_unicode).collect::<String>()),
+    }
+}
+
+#[must_use]
+pub fn card_to_string(card: Card) -> String {
+    format!("{}{}", card.value.to_char(), card.suit.to_char())
+}
+
+#[must_use]
+pub fn hand_to_string(cards: impl IntoIterator<Item = Card>) -> String {
+    cards.into_iter().map(card_to_string).collect()
+}
+
+#[must_use]
+pub fn card_to_index(card: Card) -> u8 {
+    let suit = SUITS
+        .iter()
+        .position(|s| *s == card.suit)
+        .expect("all suits should be listed");
+    let value = VALUES
+        .iter()
+        .position(|v| *v == card.value)
+        .expect("all values should be listed");
+    u8::try_from(suit * VALUES.len() + value).expect("index should be lower than the deck size")
+}
+
+/// Card matching an index from [`card_to_index`].
+///
+/// # Errors
+///
+/// Fails if the index is not lower than [`DECK_SIZE`].
+pub fn card_from_index(index: u8) -> Result<Card, CodecError> {
+    if index >= DECK_SIZE {
+        return Err(CodecError::InvalidIndex(index));
+    }
+    let index = usize::from(index);
+    Ok(Card {
+        value: VALUES[index % VALUES.len()],
+        suit: SUITS[index / VALUES.len()],
+    })
+}
+
+#[must_use]
+pub fn card_to_unicode(card: Card) -> char {
+    let base = match card.suit {
+        Suit::Spade => 0x1F0A0,
+        Suit::Heart => 0x1F0B0,
+        Suit::Diamond => 0x1F0C0,
+        Suit::Club => 0x1F0D0,
+    };
+    char::from_u32(base + unicode_rank(card.value)).expect("playing cards should be valid unicode")
+}
+
+/// Card matching a Unicode playing card glyph.
+///
+/// # Errors
+///
+/// Fails if the character is not one of the 52 cards of a standard deck (jokers and knights are rejected).
+pub fn card_from_unicode(glyph: char) -> Result<Card, CodecError> {
+    let invalid = || CodecError::InvalidCard(glyph.to_string());
+    let code = u32::from(glyph);
+    let suit = match code & !0xF {
+        0x1F0A0 => Suit::Spade,
+        0x1F0B0 => Suit::Heart,
+        0x1F0C0 => Suit::Diamond,
+        0x1F0D0 => Suit::Club,
+        _ => return Err(invalid()),
+    };
+    let value = VALUES
+        .into_iter()
+        .find(|v| unicode_rank(*v) == code & 0xF)
+        .ok_or_else(invalid)?;
+    Ok(Card { value, suit })
+}
+
+/// Rank of a value in the Unicode playing cards block, which has the ace first and a knight between jack and queen.
+fn unicode_rank(value: Value) -> u32 {
+    match value {
+        Value::Ace => 1,
+        Value::Two => 2,
+        Value::Three => 3,
+        Value::Four => 4,
+        Value::Five => 5,
+        Value::Six => 6,
+        Value::Seven => 7,
+        Value::Eight => 8,
+        Value::Nine => 9,
+        Value::Ten => 10,
+        Value::Jack => 11,
+        Value::Queen => 13,
+        Value::King => 14,
+    }
+}
+
+/// Parse a single card, whatever its notation besides JSON.
+///
+/// # Errors
+///
+/// Fails if the string is not a compact card (case insensitive), an index or a Unicode glyph.
+pub fn parse_card(s: &str) -> Result<Card, CodecError> {
+    let invalid = || CodecError::InvalidCard(s.to_string());
+    let s = s.trim();
+    if let Ok(index) = s.parse::<u8>() {
+        return card_from_index(index);
+    }
+    let mut chars = s.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(glyph), None, None) => card_from_unicode(glyph).map_err(|_| invalid()),
+        (Some(value), Some(suit), None) => {
+            let value = VALUES
+                .into_iter()
+                .find(|v| v.to_char() == value.to_ascii_uppercase())
+                .ok_or_else(invalid)?;
+            let suit = SUITS
+                .into_iter()
+                .find(|s| s.to_char() == suit.to_ascii_lowercase())
+                .ok_or_else(invalid)?;
+            Ok(Card { value, suit })
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Parse a set of cards, either concatenated compact cards or Unicode glyphs, or a comma-separated list in any
+/// notation besides JSON.
+///
+/// # Errors
+///
+/// Fails if any card is invalid.
+pub fn parse_cards(s: &str) -> Result<Vec<Card>, CodecError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(vec![]);
+    }
+    let cards = if s.contains(',') {
+        s.split(',')
+            .map(parse_card)
+            .collect::<Result<Vec<_>, _>>()?
+    } else if s.chars().all(|c| c.is_ascii_digit()) {
+        vec![parse_card(s)?]
+    } else if s.is_ascii() {
+        let chars: Vec<char> = s.chars().collect();
+        if chars.len() % 2 != 0 {
+            return Err(CodecError::InvalidCard(s.to_string()));
+        }
+        chars
+            .chunks(2)
+            .map(|c| parse_card(&c.iter().collect::<String>()))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        s.chars()
+            .map(card_from_unicode)
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    ensure_unique(&cards)?;
+    Ok(cards)
+}
+
+/// Strictly parse concatenated compact cards, as emitted on-chain by [`hand_to_string`].
+///
+/// Unlike [`parse_cards`], values must be uppercase, suits lowercase and no other notation nor whitespace is accepted.
+///
+/// # Errors
+///
+/// Fails on any invalid value or suit, on a missing suit, or if a card appears more than once.
+pub fn parse_compact(s: &str) -> Result<Vec<Card>, CodecError> {
+    let mut cards = Vec::with_capacity(s.len() / 2);
+    let mut chars = s.chars();
+    while let Some(value) = chars.next() {
+        let value = VALUES
+            .into_iter()
+            .find(|v| v.to_char() == value)
+            .ok_or(CodecError::InvalidValue(value))?;
+        let suit = chars
+            .next()
+            .ok_or(CodecError::MissingSuit(value.to_char()))?;
+        let suit = SUITS
+            .into_iter()
+            .find(|s| s.to_char() == suit)
+            .ok_or(CodecError::InvalidSuit(suit))?;
+        cards.push(Card { value, suit });
+    }
+    ensure_unique(&cards)?;
+    Ok(cards)
+}
+
+/// Check that no card appears more than once, as a single deck is used.
+///
+/// # Errors
+///
+/// Fails with the first card which is repeated.
+pub fn ensure_unique(cards: &[Card]) -> Result<(), CodecError> {
+    for (i, card) in cards.iter().enumerate() {
+        if cards[..i].contains(card) {
+            return Err(CodecError::DuplicateCard(card_to_string(*card)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deck() -> impl Iterator<Item = Card> {
+        (0..DECK_SIZE).map(|index| card_from_index(index).unwrap())
+    }
+
+    #[test]
+    fn index_round_trip() {
+        for index in 0..DECK_SIZE {
+            assert_eq!(card_to_index(card_from_index(index).unwrap()), index);
+        }
+        assert_eq!(deck().collect::<Vec<_>>().len(), usize::from(DECK_SIZE));
+        assert!(ensure_unique(&deck().collect::<Vec<_>>()).is_ok());
+    }
+
+    #[test]
+    fn index_order() {
+        assert_eq!(
+            card_from_index(0).unwrap(),
+            Card {
+                value: Value::Two,
+                suit: Suit::Spade
+            }
+        );
+        assert_eq!(
+            card_from_index(51).unwrap(),
+            Card {
+                value: Value::Ace,
+                suit: Suit::Diamond
+            }
+        );
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        for card in deck() {
+            assert_eq!(parse_card(&card_to_string(card)).unwrap(), card);
+            assert_eq!(
+                parse_card(&card_to_string(card).to_lowercase()).unwrap(),
+                card
+            );
+        }
+        assert_eq!(
+            card_to_string(Card {
+                value: Value::Ace,
+                suit: Suit::Spade
+            }),
+            "As"
+        );
+    }
+
+    #[test]
+    fn unicode_round_trip() {
+        for card in deck() {
+            let glyph = card_to_unicode(card);
+            assert_eq!(card_from_unicode(glyph).unwrap(), card);
+            assert_eq!(parse_card(&glyph.to_string()).unwrap(), card);
+        }
+        assert_eq!(
+            card_to_unicode(Card {
+                value: Value::Ace,
+                suit: Suit::Spade
+            }),
+            '🂡'
+        );
+    }
+
+    #[test]
+    fn parse_card_index() {
+        for card in deck() {
+            assert_eq!(parse_card(&card_to_index(card).to_string()).unwrap(), card);
+        }
+    }
+
+    #[test]
+    fn encode_then_parse_cards() {
+        let cards: Vec<Card> = deck().step_by(7).collect();
+        for format in [CardFormat::Compact, CardFormat::Unicode] {
+            let encoded = encode_cards(cards.iter().copied(), format);
+            assert_eq!(parse_cards(encoded.as_str().unwrap()).unwrap(), cards);
+        }
+        let indices = cards
+            .iter()
+            .map(|c| card_to_index(*c).to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(parse_cards(&indices).unwrap(), cards);
+        assert_eq!(parse_cards("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn invalid_index() {
+        assert_eq!(card_from_index(52), Err(CodecError::InvalidIndex(52)));
+        assert_eq!(parse_card("52"), Err(CodecError::InvalidIndex(52)));
+    }
+
+    #[test]
+    fn invalid_glyphs() {
+        // knight of spades, black joker and a regular letter
+        for glyph in ['🂬', '🃏', 'A'] {
+            assert!(card_from_unicode(glyph).is_err(), "{glyph}");
+        }
+    }
+
+    #[test]
+    fn invalid_cards() {
+        for card in ["", "A", "Xs", "Ax", "Asd", "🂬"] {
+            assert!(parse_card(card).is_err(), "{card:?}");
+        }
+    }
+
+    #[test]
+    fn parse_compact_strict() {
+        let cards: Vec<Card> = deck().collect();
+        assert_eq!(
+            parse_compact(&hand_to_string(cards.clone())).unwrap(),
+            cards
+        );
+        assert_eq!(parse_compact("").unwrap(), vec![]);
+        assert_eq!(parse_compact("as"), Err(CodecError::InvalidValue('a')));
+        assert_eq!(parse_compact("AS"), Err(CodecError::InvalidSuit('S')));
+        assert_eq!(parse_compact("AsK"), Err(CodecError::MissingSuit('K')));
+        assert_eq!(parse_compact("As Kd"), Err(CodecError::InvalidValue(' ')));
+        assert_eq!(parse_compact("0,1"), Err(CodecError::InvalidValue('0')));
+        assert_eq!(parse_compact("🂡"), Err(CodecError::InvalidValue('🂡')));
+    }
+
+    #[test]
+    fn duplicate_cards() {
+        let duplicate = Err(CodecError::DuplicateCard("As".to_string()));
+        assert_eq!(parse_compact("AsKdAs"), duplicate);
+        assert_eq!(parse_cards("AsKdas"), duplicate);
+        assert_eq!(parse_cards("🂡,Kd,🂡"), duplicate);
+        assert_eq!(
+            parse_cards("12,25,12").map(|_| ()),
+            Err(CodecError::DuplicateCard(card_to_string(
+                card_from_index(12).unwrap()
+            )))
+        );
+        assert!(ensure_unique(&[]).is_ok());
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use axum::{http::StatusCode, response::IntoResponse};
+use rusqlite::Connection;
+use tokio::task::JoinError;
+
+use crate::{
+    admin::AUDIT_SCHEMA,
+    api::{ApiError, error_response},
+    api_keys::API_KEYS_SCHEMA,
+    history::ROUNDS_SCHEMA,
+    sessions::SESSIONS_SCHEMA,
+    state::DEALER_STATE_SCHEMA,
+};
+
+/// Default location of the database.
+pub const DEFAULT_DB_PATH: &str = "pokerd.sqlite3";
+
+/// The SQLite database of the backend.
+///
+/// Each concern owns its tables and the queries on them, in its own module: the hand history in
+/// [`crate::history`], the audit log in [`crate::admin`], the revoked sessions in
+/// [`crate::sessions`], the API keys in [`crate::api_keys`] and the saved dealer state in
+/// [`crate::state`].
+#[derive(Debug, Clone)]
+pub struct Store {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let conn = Connection::open(path)?;
+        for schema in [
+            ROUNDS_SCHEMA,
+            AUDIT_SCHEMA,
+            SESSIONS_SCHEMA,
+            API_KEYS_SCHEMA,
+            DEALER_STATE_SCHEMA,
+        ] {
+            conn.execute_batch(schema)?;
+        }
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// The connection to the database, which must only be held for the duration of a query.
+    pub(crate) fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .expect("database lock should not be poisoned")
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum StoreError {
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("failed to (de)serialize stored value: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("database task failed: {0}")]
+    Task(#[from] JoinError),
+
+    #[error("invalid stored value: {0}")]
+    Invalid(String),
+}
+
+impl ApiError for StoreError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    fn code(&self) -> &'static str {
+        "storage_unavailable"
+    }
+}
+
+impl IntoResponse for StoreError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
@@ -0,0 +1,165 @@
+use rs_poker::core::{Card, FlatDeck, Hand, Rankable as _};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::ranking::rank_name;
+
+/// Number of simulated deals used by default.
+pub const DEFAULT_ITERATIONS: usize = 10_000;
+
+/// Maximum number of simulated deals for a single request.
+pub const MAX_ITERATIONS: usize = 100_000;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Equity {
+    /// Percentage of the simulated deals won outright
+    pub win: f64,
+
+    /// Percentage of the simulated deals resulting in a split pot
+    pub tie: f64,
+
+    /// Category of the best hand currently made with the hole cards and the board
+    pub made_hand: String,
+
+    /// Number of opponents still in the hand
+    pub opponents: usize,
+
+    /// Number of simulated deals
+    pub iterations: usize,
+}
+
+/// Estimate the equity of a hand with a Monte Carlo simulation.
+///
+/// Only the hole cards of the player and the revealed board are known, the cards of the opponents and the rest of
+/// the board are drawn from the remaining cards for each deal.
+#[must_use]
+pub fn simulate(hole: &Hand, board: &[Card], opponents: usize, iterations: usize) -> Equity {
+    let known: Vec<Card> = hole.iter().chain(board.iter().copied()).collect();
+    let (mut wins, mut ties) = (0usize, 0usize);
+    for _ in 0..iterations {
+        let mut deck = FlatDeck::default(); // already shuffled
+        let mut draw = || loop {
+            let card = deck.deal().expect("deck should have enough cards");
+            if !known.contains(&card) {
+                return card;
+            }
+        };
+        let mut full_board = board.to_vec();
+        full_board.extend((board.len()..5).map(|_| draw()));
+
+        let mut hand = hole.clone();
+        hand.extend(full_board.iter().copied());
+        let rank = hand.rank();
+        let best_opponent = (0..opponents)
+            .map(|_| {
+                let mut hand = Hand::new_with_cards(vec![draw(), draw()]);
+                hand.extend(full_board.iter().copied());
+                hand.rank()
+            })
+            .max();
+        match best_opponent {
+            Some(best) if best > rank => {}
+            Some(best) if best == rank => ties += 1,
+            _ => wins += 1,
+        }
+    }
+    Equity {
+        win: percentage(wins, iterations),
+        tie: percentage(ties, iterations),
+        made_hand: made_hand(hole, board),
+        opponents,
+        iterations,
+    }
+}
+
+/// Category of the best hand made with the hole cards and the board revealed so far.
+#[must_use]
+pub fn made_hand(hole: &Hand, board: &[Card]) -> String {
+    if board.is_empty() {
+        // pre-flop, only the hole cards are known
+        let values: Vec<_> = hole.iter().map(|c| c.value).collect();
+        return if values.len() == 2 && values[0] == values[1] {
+            "one_pair".to_string()
+        } else {
+            "high_card".to_string()
+        };
+    }
+    let mut hand = hole.clone();
+    hand.extend(board.iter().copied());
+    rank_name(&hand.rank()).to_string()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn percentage(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    count as f64 * 100.0 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hand(cards: &str) -> Hand {
+        Hand::new_from_str(cards).unwrap()
+    }
+
+    fn board(cards: &str) -> Vec<Card> {
+        hand(cards).iter().collect()
+    }
+
+    #[test]
+    fn pocket_aces_against_one_opponent() {
+        // about 85% to win, and rarely split
+        let equity = simulate(&hand("AsAh"), &[], 1, 4_000);
+        assert!((80.0..90.0).contains(&equity.win), "{equity:?}");
+        assert!(equity.tie < 3.0, "{equity:?}");
+        assert_eq!(equity.made_hand, "one_pair");
+        assert_eq!((equity.opponents, equity.iterations), (1, 4_000));
+    }
+
+    #[test]
+    fn pre_flop() {
+        let equity = simulate(&hand("7c2d"), &[], 3, 500);
+        assert!(equity.win + equity.tie <= 100.0);
+        assert_eq!(equity.made_hand, "high_card");
+    }
+
+    #[test]
+    fn flop() {
+        let equity = simulate(&hand("AsKs"), &board("Qs7s2d"), 2, 500);
+        assert!(equity.win + equity.tie <= 100.0);
+        assert_eq!(equity.made_hand, "high_card");
+    }
+
+    #[test]
+    fn turn() {
+        let equity = simulate(&hand("AsKs"), &board("Qs7s2dJs"), 2, 500);
+        // the nut flush can only be beaten by a straight flush
+        assert!(equity.win > 90.0, "{equity:?}");
+        assert_eq!(equity.made_hand, "flush");
+    }
+
+    #[test]
+    fn river() {
+        let equity = simulate(&hand("7h7d"), &board("7c7sKdQh2c"), 4, 500);
+        assert!((equity.win - 100.0).abs() < f64::EPSILON, "{equity:?}");
+        assert_eq!(equity.made_hand, "four_of_a_kind");
+    }
+
+    #[test]
+    fn board_plays() {
+        // the royal flush on the board can't be beaten, nor improved
+        let equity = simulate(&hand("2c3d"), &board("AsKsQsJsTs"), 1, 200);
+        assert!((equity.tie - 100.0).abs() < f64::EPSILON, "{equity:?}");
+        assert_eq!(equity.made_hand, "straight_flush");
+    }
+
+    #[test]
+    fn no_iterations() {
+        let equity = simulate(&hand("AsAh"), &[], 1, 0);
+        assert!(equity.win.abs() < f64::EPSILON);
+        assert!(equity.tie.abs() < f64::EPSILON);
+    }
+}
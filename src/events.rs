@@ -0,0 +1,50 @@
+use alloy::primitives::{Address, U256};
+use rs_poker::core::{Card, Hand};
+use serde::Serialize;
+
+use crate::state::Seat;
+
+/// Capacity of the table events channel. Subscribers lagging behind by more than this many events miss the oldest
+/// ones.
+pub const EVENTS_CAPACITY: usize = 64;
+
+/// Public table events, broadcast to all connected clients.
+///
+/// These must never contain private information like the hole cards of a player.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TableEvent {
+    PlayerJoined {
+        address: Address,
+        seat: Seat,
+    },
+    PlayerLeft {
+        address: Address,
+        seat: Seat,
+    },
+    PhaseChanged {
+        /// The snake case name of the new phase, as in the table snapshot
+        phase: &'static str,
+    },
+    CardsDealt {
+        seats: Vec<Seat>,
+    },
+    Flop {
+        cards: Hand,
+    },
+    Turn {
+        card: Card,
+    },
+    River {
+        card: Card,
+    },
+    PlayerBet {
+        address: Address,
+        seat: Seat,
+        amount: U256,
+    },
+    PlayerFolded {
+        seat: Seat,
+    },
+    RoundEnded,
+}
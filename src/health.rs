@@ -0,0 +1,300 @@
+use std::{
+    env,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use alloy::primitives::U256;
+use anyhow::{Context as _, Result};
+use axum::{Json, debug_handler, extract::State, http::StatusCode};
+use serde::Serialize;
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+
+use crate::state::AppState;
+
+/// Default maximum number of blocks the listener can be behind the chain head while ready.
+pub const DEFAULT_MAX_LAG_BLOCKS: u64 = 10;
+
+/// Default maximum number of seconds since the last successful poll while ready.
+pub const DEFAULT_MAX_POLL_AGE_SECS: u64 = 30;
+
+/// Number of dealer transactions currently being submitted, and since when there has been at least one.
+static PENDING_TXS: Mutex<(usize, Option<Instant>)> = Mutex::new((0, None));
+
+/// Marks a dealer transaction as pending until dropped.
+#[derive(Debug)]
+pub struct PendingTx(());
+
+impl PendingTx {
+    #[must_use]
+    pub fn start() -> Self {
+        let mut pending = PENDING_TXS
+            .lock()
+            .expect("pending txs lock should not be poisoned");
+        pending.0 += 1;
+        pending.1.get_or_insert_with(Instant::now);
+        Self(())
+    }
+}
+
+impl Drop for PendingTx {
+    fn drop(&mut self) {
+        let mut pending = PENDING_TXS
+            .lock()
+            .expect("pending txs lock should not be poisoned");
+        pending.0 -= 1;
+        if pending.0 == 0 {
+            pending.1 = None;
+        }
+    }
+}
+
+/// Number of dealer transactions which were submitted but not mined yet.
+#[must_use]
+pub fn pending_txs() -> usize {
+    PENDING_TXS
+        .lock()
+        .expect("pending txs lock should not be poisoned")
+        .0
+}
+
+/// Since when the dealer has been waiting for its transactions to be mined, if it is.
+fn pending_txs_since() -> Option<Instant> {
+    PENDING_TXS
+        .lock()
+        .expect("pending txs lock should not be poisoned")
+        .1
+}
+
+/// Health of the connection to the chain, as last seen by the listener.
+#[derive(Debug, Clone, Default)]
+pub struct ListenerStatus {
+    pub chain_id: Option<u64>,
+
+    /// The latest block number returned by the RPC
+    pub head_block: Option<u64>,
+
+    /// When the listener last got the latest block number from the RPC
+    pub last_poll_at: Option<Instant>,
+
+    /// The balance of the dealer wallet, in wei
+    pub dealer_balance: Option<U256>,
+}
+
+/// Thresholds beyond which the dealer can't serve the table.
+#[derive(Debug, Clone)]
+pub struct ReadinessConfig {
+    pub max_lag_blocks: u64,
+    pub max_poll_age_secs: u64,
+
+    /// Balance of the dealer wallet, in wei, below which a warning is reported. The dealer stays ready, as it can
+    /// still serve the table until a transaction fails
+    pub min_dealer_balance: U256,
+}
+
+impl ReadinessConfig {
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            max_lag_blocks: env::var("READY_MAX_LAG_BLOCKS")
+                .ok()
+                .map(|lag| lag.parse())
+                .transpose()
+                .context("READY_MAX_LAG_BLOCKS environment variable")?
+                .unwrap_or(DEFAULT_MAX_LAG_BLOCKS),
+            max_poll_age_secs: env::var("READY_MAX_POLL_AGE_SECS")
+                .ok()
+                .map(|age| age.parse())
+                .transpose()
+                .context("READY_MAX_POLL_AGE_SECS environment variable")?
+                .unwrap_or(DEFAULT_MAX_POLL_AGE_SECS),
+            min_dealer_balance: env::var("READY_MIN_DEALER_BALANCE")
+                .ok()
+                .map(|balance| balance.parse())
+                .transpose()
+                .context("READY_MIN_DEALER_BALANCE environment variable")?
+                .unwrap_or(U256::from(1)),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Readiness {
+    /// Whether the dealer can serve the table
+    pub ready: bool,
+
+    /// Why the dealer can't serve the table
+    pub problems: Vec<String>,
+
+    /// What should be looked at before the dealer can't serve the table, e.g. a low balance
+    pub warnings: Vec<String>,
+
+    /// Whether the RPC answered recently
+    pub rpc_reachable: bool,
+    pub chain_id: Option<u64>,
+    pub head_block: Option<u64>,
+    pub last_processed_block: u64,
+
+    /// Number of blocks between the chain head and the last processed block
+    pub lag_blocks: Option<u64>,
+
+    /// Seconds since the last poll, not counting the time spent waiting for the dealer transactions to be mined
+    pub seconds_since_last_poll: Option<u64>,
+    pub pending_dealer_txs: usize,
+
+    /// The balance of the dealer wallet, in wei
+    #[schema(value_type = Option<String>)]
+    pub dealer_balance: Option<U256>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/live",
+    tag = "health",
+    responses(
+        (status = 200, description = "The server is running"),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn live() -> &'static str {
+    info!("endpoint called");
+    "OK"
+}
+
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "The dealer can serve the table", body = Readiness),
+        (status = 503, description = "The dealer can't serve the table", body = Readiness),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn ready(State(state): State<Arc<RwLock<AppState>>>) -> (StatusCode, Json<Readiness>) {
+    info!("endpoint called");
+    let readiness = {
+        let state = state.read().expect("state lock should not be poisoned");
+        let mut readiness = check(
+            &state.listener,
+            &state.readiness,
+            state.last_processed_block,
+        );
+        if state.shutdown.is_cancelled() {
+            readiness.ready = false;
+            readiness
+                .problems
+                .push("the dealer is shutting down".to_string());
+        }
+        readiness
+    };
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+fn check(
+    status: &ListenerStatus,
+    config: &ReadinessConfig,
+    last_processed_block: u64,
+) -> Readiness {
+    let (mut problems, mut warnings) = (vec![], vec![]);
+    let seconds_since_last_poll = status
+        .last_poll_at
+        .map(|at| poll_age(at, pending_txs_since()).as_secs());
+    let rpc_reachable = seconds_since_last_poll.is_some_and(|age| age <= config.max_poll_age_secs);
+    match seconds_since_last_poll {
+        None => problems.push("the listener has not polled the RPC yet".to_string()),
+        Some(age) if !rpc_reachable => {
+            problems.push(format!("the listener last polled the RPC {age}s ago"));
+        }
+        Some(_) => {}
+    }
+    let lag_blocks = status
+        .head_block
+        .map(|head| head.saturating_sub(last_processed_block));
+    if let Some(lag) = lag_blocks.filter(|lag| *lag > config.max_lag_blocks) {
+        problems.push(format!("the listener is {lag} blocks behind"));
+    }
+    match status.dealer_balance {
+        None => warnings.push("the dealer balance is unknown".to_string()),
+        Some(balance) if balance < config.min_dealer_balance => {
+            warnings.push(format!("the dealer balance is low: {balance} wei"));
+        }
+        Some(_) => {}
+    }
+    Readiness {
+        ready: problems.is_empty(),
+        problems,
+        warnings,
+        rpc_reachable,
+        chain_id: status.chain_id,
+        head_block: status.head_block,
+        last_processed_block,
+        lag_blocks,
+        seconds_since_last_poll,
+        pending_dealer_txs: pending_txs(),
+        dealer_balance: status.dealer_balance,
+    }
+}
+
+/// Time since the last poll, frozen while the listener waits for a dealer transaction to be mined, as it can't poll
+/// in the meantime.
+fn poll_age(last_poll_at: Instant, pending_txs_since: Option<Instant>) -> Duration {
+    match pending_txs_since {
+        Some(since) if since > last_poll_at => since - last_poll_at,
+        _ => last_poll_at.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poll_age_without_pending_txs() {
+        let last_poll_at = Instant::now() - Duration::from_secs(40);
+        assert!(poll_age(last_poll_at, None) >= Duration::from_secs(40));
+    }
+
+    #[test]
+    fn poll_age_excludes_pending_txs() {
+        let now = Instant::now();
+        let last_poll_at = now - Duration::from_secs(100);
+        // a slow transaction since 95s
+        let since = now - Duration::from_secs(95);
+        assert_eq!(poll_age(last_poll_at, Some(since)), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn poll_age_after_pending_txs_since_the_poll() {
+        // the listener polled while a transaction was pending, from another task
+        let now = Instant::now();
+        let last_poll_at = now - Duration::from_secs(10);
+        let since = now - Duration::from_secs(60);
+        assert!(poll_age(last_poll_at, Some(since)) >= Duration::from_secs(10));
+    }
+
+    #[test]
+    fn low_balance_is_only_a_warning() {
+        let status = ListenerStatus {
+            chain_id: Some(1),
+            head_block: Some(100),
+            last_poll_at: Some(Instant::now()),
+            dealer_balance: Some(U256::from(10)),
+        };
+        let config = ReadinessConfig {
+            max_lag_blocks: DEFAULT_MAX_LAG_BLOCKS,
+            max_poll_age_secs: DEFAULT_MAX_POLL_AGE_SECS,
+            min_dealer_balance: U256::from(1_000),
+        };
+        let readiness = check(&status, &config, 100);
+        assert!(readiness.ready, "{readiness:?}");
+        assert_eq!(readiness.warnings, ["the dealer balance is low: 10 wei"]);
+    }
+}
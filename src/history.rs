@@ -0,0 +1,230 @@
+use std::sync::{Arc, RwLock};
+
+use alloy::primitives::{Address, U256};
+use axum::{Json, debug_handler, extract::State, http::StatusCode, response::IntoResponse};
+use rusqlite::{params, params_from_iter, types::Value};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    api::{ApiError, ErrorBody, Query, error_response},
+    db::{Store, StoreError},
+    rounds::RoundSummary,
+    state::AppState,
+};
+
+/// Default number of rounds returned per page.
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+
+/// Maximum number of rounds returned per page.
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// Tables of the hand history.
+///
+/// Each round is stored as a JSON document, next to the columns needed to query it.
+pub(crate) const ROUNDS_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS rounds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_address TEXT NOT NULL,
+    round_id TEXT,
+    started_at INTEGER,
+    ended_at INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS rounds_table_round ON rounds (table_address, round_id);
+CREATE INDEX IF NOT EXISTS rounds_ended_at ON rounds (ended_at);
+CREATE TABLE IF NOT EXISTS round_players (
+    round INTEGER NOT NULL REFERENCES rounds (id),
+    seat INTEGER NOT NULL,
+    address TEXT NOT NULL,
+    PRIMARY KEY (round, seat)
+);
+CREATE INDEX IF NOT EXISTS round_players_address ON round_players (address);
+";
+
+impl Store {
+    /// Record a completed round, returning its ID in the store.
+    pub fn record_round(&self, round: &RoundSummary) -> Result<i64, StoreError> {
+        let data = serde_json::to_string(round)?;
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO rounds (table_address, round_id, started_at, ended_at, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                round.table_address.to_string(),
+                round.id.map(|id| id.to_string()),
+                round.started_at,
+                round.ended_at,
+                data,
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        for player in &round.players {
+            tx.execute(
+                "INSERT INTO round_players (round, seat, address) VALUES (?1, ?2, ?3)",
+                params![id, *player.seat, player.address.to_string()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Retrieve the completed rounds matching the query, most recent first.
+    pub fn query_rounds(&self, query: &HistoryQuery) -> Result<Vec<RoundSummary>, StoreError> {
+        let mut sql = String::from("SELECT r.data FROM rounds r WHERE 1 = 1");
+        let mut params: Vec<Value> = vec![];
+        if let Some(table) = query.table {
+            sql.push_str(" AND r.table_address = ?");
+            params.push(Value::Text(table.to_string()));
+        }
+        if let Some(round_id) = query.round_id {
+            sql.push_str(" AND r.round_id = ?");
+            params.push(Value::Text(U256::from(round_id).to_string()));
+        }
+        if let Some(address) = query.address {
+            sql.push_str(
+                " AND EXISTS (SELECT 1 FROM round_players p WHERE p.round = r.id AND p.address = ?)",
+            );
+            params.push(Value::Text(address.to_string()));
+        }
+        if let Some(from) = query.from {
+            sql.push_str(" AND r.ended_at >= ?");
+            params.push(Value::Integer(from));
+        }
+        if let Some(to) = query.to {
+            sql.push_str(" AND r.ended_at < ?");
+            params.push(Value::Integer(to));
+        }
+        sql.push_str(" ORDER BY r.ended_at DESC, r.id DESC LIMIT ? OFFSET ?");
+        params.push(Value::Integer(query.limit().into()));
+        params.push(Value::Integer(query.offset.unwrap_or_default().into()));
+
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(params), |row| row.get::<_, String>(0))?;
+        let mut rounds = vec![];
+        for data in rows {
+            rounds.push(serde_json::from_str(&data?)?);
+        }
+        Ok(rounds)
+    }
+
+    /// Retrieve all the completed rounds in which a wallet was dealt in, oldest first.
+    pub fn rounds_for_player(&self, address: Address) -> Result<Vec<RoundSummary>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT r.data FROM rounds r JOIN round_players p ON p.round = r.id WHERE p.address = ?1 ORDER BY r.ended_at, r.id",
+        )?;
+        let rows = stmt.query_map(params![address.to_string()], |row| row.get::<_, String>(0))?;
+        let mut rounds = vec![];
+        for data in rows {
+            rounds.push(serde_json::from_str(&data?)?);
+        }
+        Ok(rounds)
+    }
+}
+/// Filters and pagination for the hand history.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct HistoryQuery {
+    /// The address of the table contract
+    #[param(value_type = Option<String>)]
+    pub table: Option<Address>,
+
+    /// The on-chain round ID
+    pub round_id: Option<u64>,
+
+    /// Only rounds in which this wallet was dealt in
+    #[param(value_type = Option<String>)]
+    pub address: Option<Address>,
+
+    /// Unix timestamp, inclusive
+    pub from: Option<i64>,
+
+    /// Unix timestamp, exclusive
+    pub to: Option<i64>,
+
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl HistoryQuery {
+    #[must_use]
+    pub fn limit(&self) -> u32 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HistoryPage {
+    pub rounds: Vec<RoundSummary>,
+    pub limit: u32,
+    pub offset: u32,
+
+    /// The offset of the next page, if there might be one
+    pub next_offset: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/history",
+    tag = "history",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "Completed rounds matching the query, most recent first", body = HistoryPage),
+        (status = 400, description = "Invalid query", body = ErrorBody),
+        (status = 500, description = "The hand history is not available", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn history(
+    Query(query): Query<HistoryQuery>,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<HistoryPage>, HistoryError> {
+    info!("endpoint called");
+    let store = {
+        state
+            .read()
+            .expect("state lock should not be poisoned")
+            .store
+            .clone()
+    };
+    let (limit, offset) = (query.limit(), query.offset.unwrap_or_default());
+    let rounds = tokio::task::spawn_blocking(move || store.query_rounds(&query))
+        .await
+        .map_err(StoreError::from)??;
+    #[allow(clippy::cast_possible_truncation)] // the page size is capped
+    let next_offset = (rounds.len() == limit as usize).then(|| offset + rounds.len() as u32);
+    Ok(Json(HistoryPage {
+        rounds,
+        limit,
+        offset,
+        next_offset,
+    }))
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum HistoryError {
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+impl ApiError for HistoryError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    fn code(&self) -> &'static str {
+        "history_unavailable"
+    }
+}
+
+impl IntoResponse for HistoryError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
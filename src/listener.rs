@@ -1,15 +1,14 @@
 use std::{
-    fmt::Write,
     sync::{Arc, RwLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use IPokerTable::{currentPhaseReturn, playerIndicesReturn};
+use IPokerTable::{currentPhaseReturn, currentRoundIdReturn, playerIndicesReturn};
 use alloy::{
     contract::{CallBuilder, CallDecoder},
     eips::eip1559::Eip1559Estimation,
     network::Ethereum,
-    primitives::{Address, U256},
+    primitives::{Address, B256, U256},
     providers::{Provider, ProviderBuilder},
     rpc::{
         self,
@@ -19,12 +18,26 @@ use alloy::{
     transports::{http::Http, layers::RetryBackoffLayer},
 };
 use anyhow::{Context as _, Result};
-use rs_poker::core::{Card, Hand};
-use tokio::time::MissedTickBehavior;
-use tracing::{debug, info, trace, warn};
+use metrics::{counter, gauge, histogram};
+use rs_poker::core::Card;
+use tokio::{sync::mpsc, time::MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, trace, warn};
 
-use crate::state::{GamePhase, MAX_PLAYERS, TablePlayer};
-use crate::{bindings::IPokerTable, state::AppState};
+use crate::state::{Bet, DealerTx, MAX_PLAYERS, TablePlayer};
+use crate::{
+    admin::{AdminAction, AdminCommand},
+    bindings::IPokerTable,
+    codec::{self, card_to_string, hand_to_string},
+    events::TableEvent,
+    health::PendingTx,
+    rounds::RoundSummary,
+    state::AppState,
+    telemetry::{
+        DEALER_FEES, DEALER_GAS_USED, DEALER_TX_DURATION, DEALER_TX_FAILURES, DEALER_TX_RETRIES,
+        DEALER_TXS, LISTENER_LAG, LOGS_PROCESSED, PHASE_DURATION, ROUNDS_COMPLETED, ROUNDS_STARTED,
+    },
+};
 
 const ALL_EVENTS: [&str; 7] = [
     IPokerTable::PlayerJoined::SIGNATURE,
@@ -36,8 +49,16 @@ const ALL_EVENTS: [&str; 7] = [
     IPokerTable::ShowdownEnded::SIGNATURE,
 ];
 
+/// Maximum number of blocks to replay when resuming from a saved state, beyond which the state is
+/// considered stale and re-synced from the chain.
+const MAX_RESUME_BLOCKS: u64 = 10_000;
+
 #[allow(clippy::too_many_lines)]
-pub async fn listen(state: Arc<RwLock<AppState>>) -> Result<()> {
+pub async fn listen(
+    state: Arc<RwLock<AppState>>,
+    mut commands: mpsc::Receiver<AdminCommand>,
+    shutdown: CancellationToken,
+) -> Result<()> {
     let (rpc_url, signer, table_address, mut last_processed_block) = {
         let state = state.read().unwrap();
         (
@@ -66,42 +87,30 @@ pub async fn listen(state: Arc<RwLock<AppState>>) -> Result<()> {
 
     let table = IPokerTable::IPokerTableInstance::new(table_address, &provider);
 
-    let currentPhaseReturn { phase } = table
-        .currentPhase()
-        .call()
+    let chain_id = provider.get_chain_id().await.context("getting chain ID")?;
+    state.write().unwrap().listener.chain_id = Some(chain_id);
+
+    let latest_block = provider
+        .get_block_number()
         .await
-        .context("getting current phase")?;
-    if !matches!(phase, IPokerTable::GamePhases::WaitingForPlayers) {
-        warn!("a round is already ongoing, need to cancel");
-        let tx = table.cancelCurrentRound();
-        let receipt = submit_tx_with_retry(&provider, wallet, tx)
-            .await
-            .context("sending round cancellation tx")?;
-        let hash = receipt.transaction_hash;
-        if receipt.status() {
-            info!("transaction {hash} succeeded");
-        } else {
-            warn!("transaction {hash} reverted");
-        }
-        info!("cancelled current round");
+        .context("getting latest block number")?;
+    if last_processed_block != 0
+        && latest_block.saturating_sub(last_processed_block) > MAX_RESUME_BLOCKS
+    {
+        warn!(
+            last_processed_block,
+            latest_block, "saved state is too old, syncing from the chain instead"
+        );
+        let mut state = state.write().unwrap();
+        state.cancel_round();
+        state.last_processed_block = 0;
+        last_processed_block = 0;
     }
-
-    // retrieve existing players
-    for seat in 0..MAX_PLAYERS {
-        let playerIndicesReturn { player } = table
-            .playerIndices(U256::from(seat))
-            .call()
-            .await
-            .context("getting player seat")?;
-        if player == Address::ZERO {
-            debug!("no player for seat {seat}");
-        } else {
-            info!(?player, seat, "found player");
-            state.write().unwrap().table_players.push(TablePlayer {
-                address: player,
-                seat: seat.into(),
-            });
-        }
+    if last_processed_block == 0 {
+        sync_from_chain(&provider, &state, &table, wallet).await?;
+    } else {
+        // the logs emitted while we were down are replayed below
+        info!(last_processed_block, "resuming from saved state");
     }
 
     // let filter = Filter::new()
@@ -117,11 +126,8 @@ pub async fn listen(state: Arc<RwLock<AppState>>) -> Result<()> {
     //     .context("registering log filter")?;
     // let mut stream = poller.into_stream().flat_map(stream::iter);
     if last_processed_block == 0 {
-        last_processed_block = provider
-            .get_block_number()
-            .await
-            .context("getting latest block number")?
-            - 1;
+        last_processed_block = latest_block - 1;
+        state.write().unwrap().last_processed_block = last_processed_block;
         debug!("processing logs from latest block {last_processed_block}");
     }
 
@@ -129,58 +135,38 @@ pub async fn listen(state: Arc<RwLock<AppState>>) -> Result<()> {
     interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
     loop {
-        interval.tick().await;
+        tokio::select! {
+            _ = interval.tick() => {}
+            // only checked between two batches of logs, so that the dealer transactions they
+            // trigger are mined and the state matches `last_processed_block`
+            () = shutdown.cancelled() => {
+                info!(last_processed_block, "listener stopped");
+                return Ok(());
+            }
+            Some(command) = commands.recv() => {
+                let outcome =
+                    handle_admin_command(&provider, &state, &table, wallet, command.action).await;
+                if let Err(err) = &outcome {
+                    warn!(?err, action = command.action.name(), "admin action failed");
+                }
+                // the operator might have given up waiting
+                let _ = command.reply.send(outcome);
+                continue;
+            }
+        }
         let latest_block = provider
             .get_block_number()
             .await
             .context("getting latest block number")?;
+        update_listener_status(&provider, &state, wallet, latest_block).await;
+        #[allow(clippy::cast_precision_loss)]
+        gauge!(LISTENER_LAG).set(latest_block.saturating_sub(last_processed_block) as f64);
         if latest_block <= last_processed_block {
             continue;
         }
         trace!(latest_block);
-        let filter = Filter::new()
-            .address(table_address)
-            .events(ALL_EVENTS)
-            .from_block(last_processed_block + 1)
-            .to_block(latest_block);
-        let logs = provider.get_logs(&filter).await.with_context(|| {
-            format!(
-                "getting logs for block range {} - {latest_block}",
-                last_processed_block + 1
-            )
-        })?;
-        let mut logs: Vec<_> = logs
-            .into_iter()
-            .filter(|l| l.block_number.is_some() && l.log_index.is_some())
-            .collect();
-        // make sure they are sorted
-        logs.sort_by(|a, b| {
-            a.block_number
-                .unwrap()
-                .cmp(&b.block_number.unwrap())
-                .then(a.log_index.unwrap().cmp(&b.log_index.unwrap()))
-        });
-        if logs.is_empty() {
-            trace!(
-                start = last_processed_block + 1,
-                end = latest_block,
-                "no logs"
-            );
-        } else {
-            debug!(
-                start = last_processed_block + 1,
-                end = latest_block,
-                logs = logs.len(),
-                "got logs"
-            );
-        }
-        for log in logs {
-            handle_event(&provider, Arc::clone(&state), &table, wallet, log)
-                .await
-                .context("processing log")?;
-        }
+        process_logs(&provider, &state, &table, wallet, latest_block).await?;
         last_processed_block = latest_block;
-        state.write().unwrap().last_processed_block = last_processed_block;
     }
 
     // while let Some(block_hash) = stream.next().await {
@@ -212,91 +198,83 @@ pub async fn handle_event<P: Provider>(
     let Some(topic) = log.topic0() else {
         return Ok(());
     };
+    counter!(LOGS_PROCESSED, "event" => event_name(topic)).increment(1);
     match *topic {
         IPokerTable::PlayerJoined::SIGNATURE_HASH => {
             let log = IPokerTable::PlayerJoined::decode_log(&log.inner, true)
                 .context("decoding log for PlayerJoined")?;
-            let num_players = {
+            {
                 let mut state = state.write().unwrap();
                 let seat = log.indexOnTable.try_into()?;
                 state.table_players.push(TablePlayer {
-                    address: log.address,
+                    address: log.player,
+                    seat,
+                });
+                state.buy_ins.insert(log.player, log.buyIn);
+                info!(player = ?log.player, seat = seat.to_string(), "new player joined");
+                state.publish(TableEvent::PlayerJoined {
+                    address: log.player,
                     seat,
                 });
-                info!(player = ?log.address, seat = seat.to_string(), "new player joined");
-                state.table_players.len()
-            };
-            if num_players > 1 {
-                info!("we have {num_players} players, round starting");
-                let tx =
-                    table.setCurrentPhase(IPokerTable::GamePhases::WaitingForDealer, String::new());
-                let receipt = submit_tx_with_retry(&provider, wallet, tx)
-                    .await
-                    .context("submitting tx")?;
-                let hash = receipt.transaction_hash;
-                if receipt.status() {
-                    info!("transaction {hash} succeeded");
-                } else {
-                    warn!("transaction {hash} reverted");
-                }
             }
+            start_round_if_ready(&provider, &state, table, wallet).await?;
         }
         IPokerTable::PlayerLeft::SIGNATURE_HASH => {
             let log = IPokerTable::PlayerLeft::decode_log(&log.inner, true)
                 .context("decoding log for PlayerLeft")?;
             {
                 let mut state = state.write().unwrap();
-                state.table_players.retain(|p| p.address != log.address);
+                state.table_players.retain(|p| p.address != log.player);
+                state.buy_ins.remove(&log.player);
                 let seat = log.indexOnTable.try_into()?;
-                state
-                    .remove_player(seat)
-                    .context("removing player from round because they left")?;
-                info!(player = ?log.address, seat = seat.to_string(), "player left");
+                state.phase.remove_player(seat);
+                info!(player = ?log.player, seat = seat.to_string(), "player left");
+                state.publish(TableEvent::PlayerLeft {
+                    address: log.player,
+                    seat,
+                });
             }
         }
         IPokerTable::PhaseChanged::SIGNATURE_HASH => {
             let log = IPokerTable::PhaseChanged::decode_log(&log.inner, true)
                 .context("decoding log for PhaseChanged")?;
             debug!(new_phase = ?log.newPhase, "phase changed");
+            {
+                let mut state = state.write().unwrap();
+                histogram!(PHASE_DURATION, "phase" => chain_phase_name(&log.previousPhase))
+                    .record(state.phase_started_at.elapsed().as_secs_f64());
+                state.phase_started_at = Instant::now();
+            }
+            state.read().unwrap().publish(TableEvent::PhaseChanged {
+                phase: chain_phase_name(&log.newPhase),
+            });
             match log.newPhase {
                 IPokerTable::GamePhases::WaitingForPlayers => {
                     info!("entered waiting for players phase");
-                    let num_players = {
-                        let state = state.read().unwrap();
-                        state.table_players.len()
-                    };
-                    if num_players > 1 {
-                        info!("we have {num_players} players, round starting");
-                        let tx = table.setCurrentPhase(
-                            IPokerTable::GamePhases::WaitingForDealer,
-                            String::new(),
-                        );
-                        let receipt = submit_tx_with_retry(&provider, wallet, tx)
-                            .await
-                            .context("submitting tx")?;
-                        let hash = receipt.transaction_hash;
-                        if receipt.status() {
-                            info!("transaction {hash} succeeded");
-                        } else {
-                            warn!("transaction {hash} reverted");
-                        }
-                    }
+                    start_round_if_ready(&provider, &state, table, wallet).await?;
                 }
                 IPokerTable::GamePhases::WaitingForDealer => {
+                    let currentRoundIdReturn { round } = table
+                        .currentRoundId()
+                        .call()
+                        .await
+                        .context("getting current round ID")?;
                     {
-                        state.write().unwrap().set_ready();
+                        let mut state = state.write().unwrap();
+                        state.round.id = Some(round);
+                        state.set_ready();
+                        let participants = state.table_players.clone();
+                        state
+                            .start_game(&participants)
+                            .context("dealing starting hands")?;
                     }
+                    counter!(ROUNDS_STARTED).increment(1);
                     info!("starting pre-flop phase");
                     let tx = table.setCurrentPhase(IPokerTable::GamePhases::PreFlop, String::new());
                     let receipt = submit_tx_with_retry(&provider, wallet, tx)
                         .await
                         .context("submitting tx")?;
-                    let hash = receipt.transaction_hash;
-                    if receipt.status() {
-                        info!("transaction {hash} succeeded");
-                    } else {
-                        warn!("transaction {hash} reverted");
-                    }
+                    record_dealer_tx(&state, "setCurrentPhase(PreFlop)", &receipt);
                 }
                 IPokerTable::GamePhases::PreFlop => {
                     info!("started pre-flop phase");
@@ -311,17 +289,14 @@ pub async fn handle_event<P: Provider>(
                         state.reveal_flop().context("revealing flop")?
                     };
                     info!(?flop, "starting flop phase");
-                    let tx =
-                        table.setCurrentPhase(IPokerTable::GamePhases::Flop, hand_to_string(&flop));
+                    let tx = table.setCurrentPhase(
+                        IPokerTable::GamePhases::Flop,
+                        hand_to_string(flop.iter()),
+                    );
                     let receipt = submit_tx_with_retry(&provider, wallet, tx)
                         .await
                         .context("submitting tx")?;
-                    let hash = receipt.transaction_hash;
-                    if receipt.status() {
-                        info!("transaction {hash} succeeded");
-                    } else {
-                        warn!("transaction {hash} reverted");
-                    }
+                    record_dealer_tx(&state, "setCurrentPhase(Flop)", &receipt);
                 }
                 IPokerTable::GamePhases::Flop => {
                     info!("started flop phase");
@@ -341,12 +316,7 @@ pub async fn handle_event<P: Provider>(
                     let receipt = submit_tx_with_retry(&provider, wallet, tx)
                         .await
                         .context("submitting tx")?;
-                    let hash = receipt.transaction_hash;
-                    if receipt.status() {
-                        info!("transaction {hash} succeeded");
-                    } else {
-                        warn!("transaction {hash} reverted");
-                    }
+                    record_dealer_tx(&state, "setCurrentPhase(Turn)", &receipt);
                 }
                 IPokerTable::GamePhases::Turn => {
                     info!("started turn phase");
@@ -366,12 +336,7 @@ pub async fn handle_event<P: Provider>(
                     let receipt = submit_tx_with_retry(&provider, wallet, tx)
                         .await
                         .context("submitting tx")?;
-                    let hash = receipt.transaction_hash;
-                    if receipt.status() {
-                        info!("transaction {hash} succeeded");
-                    } else {
-                        warn!("transaction {hash} reverted");
-                    }
+                    record_dealer_tx(&state, "setCurrentPhase(River)", &receipt);
                 }
                 IPokerTable::GamePhases::River => {
                     info!("started river phase");
@@ -391,8 +356,8 @@ pub async fn handle_event<P: Provider>(
                             .map(|seat| {
                                 hands
                                     .iter()
-                                    .find(|(s, _)| **s == seat)
-                                    .map_or(String::new(), |(_, h)| hand_to_string(h))
+                                    .find(|h| *h.seat == seat)
+                                    .map_or(String::new(), |h| hand_to_string(h.cards.iter()))
                             })
                             .collect(),
                         winners.into_iter().map(Into::into).collect(),
@@ -400,35 +365,69 @@ pub async fn handle_event<P: Provider>(
                     let receipt = submit_tx_with_retry(&provider, wallet, tx)
                         .await
                         .context("submitting tx")?;
-                    let hash = receipt.transaction_hash;
-                    if receipt.status() {
-                        info!("transaction {hash} succeeded");
-                    } else {
-                        warn!("transaction {hash} reverted");
-                    }
+                    record_dealer_tx(&state, "revealShowdownResult", &receipt);
                 }
                 IPokerTable::GamePhases::__Invalid => {
                     return Ok(());
                 }
             }
         }
-        IPokerTable::PlayerBet::SIGNATURE_HASH => {}
+        IPokerTable::PlayerBet::SIGNATURE_HASH => {
+            let log = IPokerTable::PlayerBet::decode_log(&log.inner, true)
+                .context("decoding log for PlayerBet")?;
+            let seat = log.indexOnTable.try_into()?;
+            debug!(player = ?log.player, seat = seat.to_string(), amount = ?log.betAmount, "player bet");
+            let mut state = state.write().unwrap();
+            let phase = state.phase.name().to_string();
+            state.round.bets.push(Bet {
+                address: log.player,
+                seat,
+                amount: log.betAmount,
+                phase,
+            });
+            state.publish(TableEvent::PlayerBet {
+                address: log.player,
+                seat,
+                amount: log.betAmount,
+            });
+        }
         IPokerTable::PlayerFolded::SIGNATURE_HASH => {
             let log = IPokerTable::PlayerFolded::decode_log(&log.inner, true)
                 .context("decoding log for PlayerFolded")?;
             {
                 let mut state = state.write().unwrap();
                 let seat = log.indexOnTable.try_into()?;
-                state
-                    .remove_player(seat)
-                    .context("removing player from round because they folded")?;
-                info!(player = ?log.address, seat = seat.to_string(), "player folded");
+                state.phase.remove_player(seat);
+                info!(seat = seat.to_string(), "player folded");
+                state.publish(TableEvent::PlayerFolded { seat });
             }
         }
-        IPokerTable::PlayerWonWithoutShowdown::SIGNATURE_HASH
-        | IPokerTable::ShowdownEnded::SIGNATURE_HASH => {
-            info!("game ended, resetting for new round");
-            state.write().unwrap().phase = GamePhase::default();
+        IPokerTable::PlayerWonWithoutShowdown::SIGNATURE_HASH => {
+            let log = IPokerTable::PlayerWonWithoutShowdown::decode_log(&log.inner, true)
+                .context("decoding log for PlayerWonWithoutShowdown")?;
+            let seat = log.indexOnTable.try_into()?;
+            info!(winner = ?log.winner, seat = seat.to_string(), pot = ?log.pot, "game ended without showdown, resetting for new round");
+            let summary = state
+                .write()
+                .unwrap()
+                .end_round_without_showdown(seat, log.pot);
+            record_round(&state, summary).await;
+        }
+        IPokerTable::ShowdownEnded::SIGNATURE_HASH => {
+            let log = IPokerTable::ShowdownEnded::decode_log(&log.inner, true)
+                .context("decoding log for ShowdownEnded")?;
+            info!(pot = ?log.pot, "game ended with showdown, resetting for new round");
+            verify_showdown_cards(
+                &state.read().unwrap(),
+                &log.communityCards,
+                &log.playersData,
+            );
+            let gains: Vec<_> = log.playersData.iter().map(|p| p.gains).collect();
+            let summary = state
+                .write()
+                .unwrap()
+                .end_round_with_showdown(log.pot, &gains);
+            record_round(&state, summary).await;
         }
         _ => {
             return Ok(());
@@ -437,11 +436,427 @@ pub async fn handle_event<P: Provider>(
     Ok(())
 }
 
+/// Record that the RPC answered, along with the dealer balance for the readiness checks.
+async fn update_listener_status(
+    provider: impl Provider,
+    state: &Arc<RwLock<AppState>>,
+    wallet: Address,
+    latest_block: u64,
+) {
+    let balance = provider
+        .get_balance(wallet)
+        .await
+        .inspect_err(|err| warn!(?err, "failed to get dealer balance"))
+        .ok();
+    let mut state = state.write().unwrap();
+    state.listener.head_block = Some(latest_block);
+    state.listener.last_poll_at = Some(Instant::now());
+    if balance.is_some() {
+        state.listener.dealer_balance = balance;
+    }
+}
+
+/// Process the logs emitted by the table after the last processed block, up to `latest_block`.
+async fn process_logs<P: Provider + Clone>(
+    provider: P,
+    state: &Arc<RwLock<AppState>>,
+    table: &IPokerTable::IPokerTableInstance<(), P>,
+    wallet: Address,
+    latest_block: u64,
+) -> Result<()> {
+    let last_processed_block = state.read().unwrap().last_processed_block;
+    let filter = Filter::new()
+        .address(*table.address())
+        .events(ALL_EVENTS)
+        .from_block(last_processed_block + 1)
+        .to_block(latest_block);
+    let logs = provider.get_logs(&filter).await.with_context(|| {
+        format!(
+            "getting logs for block range {} - {latest_block}",
+            last_processed_block + 1
+        )
+    })?;
+    let mut logs: Vec<_> = logs
+        .into_iter()
+        .filter(|l| l.block_number.is_some() && l.log_index.is_some())
+        .collect();
+    // make sure they are sorted
+    logs.sort_by(|a, b| {
+        a.block_number
+            .unwrap()
+            .cmp(&b.block_number.unwrap())
+            .then(a.log_index.unwrap().cmp(&b.log_index.unwrap()))
+    });
+    if logs.is_empty() {
+        trace!(
+            start = last_processed_block + 1,
+            end = latest_block,
+            "no logs"
+        );
+    } else {
+        debug!(
+            start = last_processed_block + 1,
+            end = latest_block,
+            logs = logs.len(),
+            "got logs"
+        );
+    }
+    let mut logs = logs.into_iter().peekable();
+    while let Some(log) = logs.next() {
+        let block = log.block_number;
+        handle_event(provider.clone(), Arc::clone(state), table, wallet, log)
+            .await
+            .context("processing log")?;
+        // record the progress once all the logs of a block are processed, so that a failure only replays the
+        // logs of the block on which it happened
+        if let Some(block) =
+            block.filter(|b| logs.peek().is_some_and(|l| l.block_number != Some(*b)))
+        {
+            state.write().unwrap().last_processed_block = block;
+        }
+    }
+    state.write().unwrap().last_processed_block = latest_block;
+    Ok(())
+}
+
+/// Carry out an action requested by an operator through the admin API.
+async fn handle_admin_command<P: Provider + Clone>(
+    provider: P,
+    state: &Arc<RwLock<AppState>>,
+    table: &IPokerTable::IPokerTableInstance<(), P>,
+    wallet: Address,
+    action: AdminAction,
+) -> Result<Option<DealerTx>> {
+    info!(action = action.name(), "handling admin command");
+    match action {
+        // only audited, not sent to the listener
+        AdminAction::ShowPhase | AdminAction::ListSessions | AdminAction::RevokeSessions => {
+            Ok(None)
+        }
+        AdminAction::CancelRound => {
+            let tx = table.cancelCurrentRound();
+            let receipt = submit_tx_with_retry(&provider, wallet, tx)
+                .await
+                .context("sending round cancellation tx")?;
+            let tx = record_dealer_tx(state, "cancelCurrentRound", &receipt);
+            if tx.success {
+                state.write().unwrap().cancel_round();
+                info!("cancelled current round");
+            }
+            Ok(Some(tx))
+        }
+        AdminAction::TimeoutPlayer => {
+            let tx = table.timeoutCurrentPlayer();
+            let receipt = submit_tx_with_retry(&provider, wallet, tx)
+                .await
+                .context("sending player timeout tx")?;
+            Ok(Some(record_dealer_tx(
+                state,
+                "timeoutCurrentPlayer",
+                &receipt,
+            )))
+        }
+        AdminAction::PauseAutoStart => {
+            state.write().unwrap().auto_start = false;
+            info!("paused auto-start of new rounds");
+            Ok(None)
+        }
+        AdminAction::ResumeAutoStart => {
+            let waiting = {
+                let mut state = state.write().unwrap();
+                state.auto_start = true;
+                state.phase.is_waiting_for_players()
+            };
+            info!("resumed auto-start of new rounds");
+            if !waiting {
+                return Ok(None);
+            }
+            start_round_if_ready(&provider, state, table, wallet).await
+        }
+        AdminAction::Resync => sync_from_chain(provider, state, table, wallet).await,
+    }
+}
+
+/// Align the local state with the table contract, returning the cancellation transaction if one was needed.
+///
+/// The logs which were not processed yet are processed first, so that a state which is merely behind the chain
+/// catches up. A round which is ongoing on-chain can't be resumed if the dealer lost track of it though, since the
+/// hole cards are not known anymore, so it is cancelled.
+async fn sync_from_chain<P: Provider + Clone>(
+    provider: P,
+    state: &Arc<RwLock<AppState>>,
+    table: &IPokerTable::IPokerTableInstance<(), P>,
+    wallet: Address,
+) -> Result<Option<DealerTx>> {
+    let last_processed_block = state.read().unwrap().last_processed_block;
+    if last_processed_block != 0 {
+        let latest_block = provider
+            .get_block_number()
+            .await
+            .context("getting latest block number")?;
+        if latest_block > last_processed_block {
+            process_logs(provider.clone(), state, table, wallet, latest_block).await?;
+        }
+    }
+    let currentPhaseReturn { phase } = table
+        .currentPhase()
+        .call()
+        .await
+        .context("getting current phase")?;
+    let mut cancellation = None;
+    if !matches!(phase, IPokerTable::GamePhases::WaitingForPlayers) {
+        let currentRoundIdReturn { round } = table
+            .currentRoundId()
+            .call()
+            .await
+            .context("getting current round ID")?;
+        let (local_phase, dealt, local_round) = {
+            let state = state.read().unwrap();
+            (
+                state.phase.name(),
+                state.get_players().is_some(),
+                state.round.id,
+            )
+        };
+        let recoverable = if dealt {
+            local_round.is_none_or(|id| id == round)
+        } else {
+            // the cards are dealt when processing the phase change, which might be in a block which was not
+            // processed yet
+            last_processed_block != 0 && matches!(phase, IPokerTable::GamePhases::WaitingForDealer)
+        };
+        if recoverable {
+            if chain_phase_name(&phase) != local_phase {
+                warn!(chain_phase = ?phase, local_phase, "local state is behind the chain, it catches up with the next logs");
+            }
+        } else {
+            warn!(chain_phase = ?phase, local_phase, ?round, ?local_round, "a round is ongoing without known hole cards, need to cancel");
+            let tx = table.cancelCurrentRound();
+            let receipt = submit_tx_with_retry(&provider, wallet, tx)
+                .await
+                .context("sending round cancellation tx")?;
+            let tx = record_dealer_tx(state, "cancelCurrentRound", &receipt);
+            if tx.success {
+                state.write().unwrap().cancel_round();
+                info!("cancelled current round");
+            }
+            cancellation = Some(tx);
+        }
+    }
+
+    // retrieve existing players
+    let mut players = vec![];
+    for seat in 0..MAX_PLAYERS {
+        let playerIndicesReturn { player } = table
+            .playerIndices(U256::from(seat))
+            .call()
+            .await
+            .context("getting player seat")?;
+        if player == Address::ZERO {
+            debug!("no player for seat {seat}");
+        } else {
+            info!(?player, seat, "found player");
+            players.push(TablePlayer {
+                address: player,
+                seat: seat.into(),
+            });
+        }
+    }
+    {
+        let mut state = state.write().unwrap();
+        state
+            .buy_ins
+            .retain(|address, _| players.iter().any(|p| p.address == *address));
+        state.table_players = players;
+    }
+    Ok(cancellation)
+}
+
+/// Ask the contract to start a new round if enough players are seated, unless operators paused it.
+async fn start_round_if_ready<P: Provider>(
+    provider: impl Provider,
+    state: &Arc<RwLock<AppState>>,
+    table: &IPokerTable::IPokerTableInstance<(), P>,
+    wallet: Address,
+) -> Result<Option<DealerTx>> {
+    let (num_players, auto_start) = {
+        let state = state.read().unwrap();
+        (state.table_players.len(), state.auto_start)
+    };
+    if num_players < 2 {
+        return Ok(None);
+    }
+    if !auto_start {
+        info!("we have {num_players} players, but auto-start is paused");
+        return Ok(None);
+    }
+    info!("we have {num_players} players, round starting");
+    let tx = table.setCurrentPhase(IPokerTable::GamePhases::WaitingForDealer, String::new());
+    let receipt = submit_tx_with_retry(&provider, wallet, tx)
+        .await
+        .context("submitting tx")?;
+    Ok(Some(record_dealer_tx(
+        state,
+        "setCurrentPhase(WaitingForDealer)",
+        &receipt,
+    )))
+}
+
+/// Name of a contract event, for the metrics.
+fn event_name(topic: &B256) -> &'static str {
+    match *topic {
+        IPokerTable::PlayerJoined::SIGNATURE_HASH => "player_joined",
+        IPokerTable::PlayerLeft::SIGNATURE_HASH => "player_left",
+        IPokerTable::PhaseChanged::SIGNATURE_HASH => "phase_changed",
+        IPokerTable::PlayerBet::SIGNATURE_HASH => "player_bet",
+        IPokerTable::PlayerFolded::SIGNATURE_HASH => "player_folded",
+        IPokerTable::PlayerWonWithoutShowdown::SIGNATURE_HASH => "player_won_without_showdown",
+        IPokerTable::ShowdownEnded::SIGNATURE_HASH => "showdown_ended",
+        _ => "unknown",
+    }
+}
+
+/// Name of an on-chain phase, matching [`crate::state::GamePhase::name`].
+fn chain_phase_name(phase: &IPokerTable::GamePhases) -> &'static str {
+    match phase {
+        IPokerTable::GamePhases::WaitingForPlayers => "waiting_for_players",
+        IPokerTable::GamePhases::WaitingForDealer => "waiting_for_dealer",
+        IPokerTable::GamePhases::PreFlop => "pre_flop",
+        IPokerTable::GamePhases::WaitingForFlop => "waiting_for_flop",
+        IPokerTable::GamePhases::Flop => "flop",
+        IPokerTable::GamePhases::WaitingForTurn => "waiting_for_turn",
+        IPokerTable::GamePhases::Turn => "turn",
+        IPokerTable::GamePhases::WaitingForRiver => "waiting_for_river",
+        IPokerTable::GamePhases::River => "river",
+        IPokerTable::GamePhases::WaitingForResult => "waiting_for_result",
+        IPokerTable::GamePhases::__Invalid => "invalid",
+    }
+}
+
+/// Cross-check the cards emitted by the contract at showdown against the ones which were dealt.
+///
+/// Any mismatch is raised as an alert, since it means either the dealer or the contract is misbehaving.
+fn verify_showdown_cards(
+    state: &AppState,
+    community_cards: &str,
+    results: &[IPokerTable::PlayerResult],
+) {
+    let Some(players) = state.get_players() else {
+        warn!("missed the beginning of the round, cannot verify the showdown cards");
+        return;
+    };
+    let dealt_board = state.get_board();
+    let mut emitted = match codec::parse_compact(community_cards) {
+        Ok(board) => {
+            if !same_cards(&board, &dealt_board) {
+                error!(
+                    alert = "card_mismatch",
+                    emitted = community_cards,
+                    dealt = %hand_to_string(dealt_board),
+                    "community cards emitted by the contract do not match the dealt ones"
+                );
+            }
+            board
+        }
+        Err(err) => {
+            error!(
+                alert = "invalid_cards",
+                ?err,
+                community_cards,
+                "contract emitted invalid community cards"
+            );
+            vec![]
+        }
+    };
+    for (seat, result) in results.iter().enumerate() {
+        let dealt: Vec<Card> = players
+            .iter()
+            .find(|p| *p.seat == seat)
+            .map(|p| p.starting_hand.iter().collect())
+            .unwrap_or_default();
+        match codec::parse_compact(&result.cards) {
+            Ok(cards) => {
+                if !same_cards(&cards, &dealt) {
+                    error!(
+                        alert = "card_mismatch",
+                        seat,
+                        emitted = %result.cards,
+                        dealt = %hand_to_string(dealt),
+                        "hole cards emitted by the contract do not match the dealt ones"
+                    );
+                }
+                emitted.extend(cards);
+            }
+            Err(err) => {
+                error!(
+                    alert = "invalid_cards",
+                    ?err,
+                    seat,
+                    cards = %result.cards,
+                    "contract emitted invalid hole cards"
+                );
+            }
+        }
+    }
+    if let Err(err) = codec::ensure_unique(&emitted) {
+        error!(
+            alert = "card_mismatch",
+            ?err,
+            "contract emitted the same card more than once"
+        );
+    }
+}
+
+/// Whether both sets contain the same cards, in any order.
+fn same_cards(a: &[Card], b: &[Card]) -> bool {
+    a.len() == b.len() && a.iter().all(|c| b.contains(c))
+}
+
+/// Persist a completed round to the hand history.
+///
+/// Failures are logged but otherwise ignored, so that the table keeps running.
+async fn record_round(state: &Arc<RwLock<AppState>>, summary: RoundSummary) {
+    counter!(ROUNDS_COMPLETED, "showdown" => summary.showdown.to_string()).increment(1);
+    let store = state.read().unwrap().store.clone();
+    match tokio::task::spawn_blocking(move || store.record_round(&summary)).await {
+        Ok(Ok(id)) => debug!(id, "round recorded to history"),
+        Ok(Err(err)) => error!(?err, "failed to record round to history"),
+        Err(err) => error!(?err, "database task failed"),
+    }
+}
+
+/// Log the outcome of a dealer transaction and record it for the current round.
+fn record_dealer_tx(
+    state: &Arc<RwLock<AppState>>,
+    call: &str,
+    receipt: &TransactionReceipt,
+) -> DealerTx {
+    let hash = receipt.transaction_hash;
+    if receipt.status() {
+        info!("transaction {hash} succeeded");
+    } else {
+        warn!("transaction {hash} reverted");
+    }
+    let tx = DealerTx {
+        call: call.to_string(),
+        hash,
+        success: receipt.status(),
+    };
+    let status = if tx.success { "success" } else { "reverted" };
+    counter!(DEALER_TXS, "call" => tx.call.clone(), "status" => status).increment(1);
+    counter!(DEALER_GAS_USED).increment(receipt.gas_used);
+    let fees = u128::from(receipt.gas_used) * receipt.effective_gas_price / 1_000_000_000;
+    counter!(DEALER_FEES).increment(u64::try_from(fees).unwrap_or(u64::MAX));
+    state.write().unwrap().round.dealer_txs.push(tx.clone());
+    tx
+}
+
 pub async fn submit_tx_with_retry<T: Clone, P: Provider + Clone, D: CallDecoder + Clone>(
     provider: impl Provider,
     wallet: Address,
     tx: CallBuilder<T, P, D, Ethereum>,
 ) -> Result<TransactionReceipt> {
+    let _pending = PendingTx::start();
     // set a fixed nonce so we can re-submit with more gas
     let mut nonce = provider
         .get_transaction_count(wallet)
@@ -453,6 +868,7 @@ pub async fn submit_tx_with_retry<T: Clone, P: Provider + Clone, D: CallDecoder
         max_priority_fee_per_gas: 0,
     };
     let mut tries = 0usize;
+    let started = Instant::now();
     loop {
         // if the new gas is not enough to re-submit the transaction, increase it, otherwise use the new gas estimate
         update_gas(
@@ -477,6 +893,7 @@ pub async fn submit_tx_with_retry<T: Clone, P: Provider + Clone, D: CallDecoder
             .await
         {
             Ok(receipt) => {
+                histogram!(DEALER_TX_DURATION).record(started.elapsed().as_secs_f64());
                 return Ok(receipt);
             }
             Err(e) => {
@@ -495,10 +912,12 @@ pub async fn submit_tx_with_retry<T: Clone, P: Provider + Clone, D: CallDecoder
                             .context("failed to get nonce for the bot wallet")?;
                     }
                     9.. => {
+                        counter!(DEALER_TX_FAILURES).increment(1);
                         return Err(e).context("could not get receipt after many tries");
                     }
                 }
                 tries += 1;
+                counter!(DEALER_TX_RETRIES).increment(1);
                 warn!(tries, err = ?e, "transaction {hash} was not mined after timeout, retrying with more gas");
             }
         }
@@ -515,17 +934,3 @@ fn update_gas(old: &mut Eip1559Estimation, new_estimate: Eip1559Estimation) {
         .max_priority_fee_per_gas
         .max((old.max_priority_fee_per_gas * 110).div_ceil(100));
 }
-
-#[must_use]
-pub fn card_to_string(card: Card) -> String {
-    format!("{}{}", card.value.to_char(), card.suit.to_char())
-}
-
-#[must_use]
-pub fn hand_to_string(hand: &Hand) -> String {
-    hand.iter().fold(String::new(), |mut output, c| {
-        let _ = write!(output, "{}", c.value.to_char());
-        let _ = write!(output, "{}", c.suit.to_char());
-        output
-    })
-}
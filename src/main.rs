@@ -1,30 +1,83 @@
 //! Backend service for
 use std::{
+    collections::HashMap,
     env,
+    future::IntoFuture as _,
     sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use alloy::{hex::FromHex as _, primitives::B256, signers::local::PrivateKeySigner};
 use anyhow::{Context as _, Result};
 use axum::{
-    Json, Router,
+    Router,
     http::StatusCode,
+    middleware,
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post, put},
 };
-use serde_json::json;
-use tracing::{debug, info, instrument, level_filters::LevelFilter, warn};
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, level_filters::LevelFilter, warn};
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt as _, util::SubscriberInitExt as _};
 
-use cards::{flop, hand, river, turn};
-use privy::{Privy, PrivyConfig};
-use state::{AppState, GamePhase};
+use admin::{
+    ADMIN_COMMANDS_CAPACITY, AdminConfig, cancel_round, list_sessions, pause_auto_start,
+    resume_auto_start, resync, revoke_sessions, show_phase, timeout_player,
+};
+use api::{API_VERSION, ApiError, error_response, not_found};
+use api_keys::{ApiKeys, create_api_key, revoke_api_key};
+use auth::{DEFAULT_DEV_TOKEN_TTL, DevAuth};
+use cards::{flop, hand, hand_equity, river, turn};
+use db::{DEFAULT_DB_PATH, Store};
+use events::EVENTS_CAPACITY;
+use health::{ListenerStatus, ReadinessConfig, live, pending_txs, ready};
+use history::history;
+use openapi::openapi;
+use policy::PolicyConfig;
+use rounds::{DEFAULT_ROUND_HISTORY_SIZE, RoundHistory, latest_round, round};
+use sealed::{delete_hand_key, register_hand_key};
+use sessions::{SessionRegistry, SessionsConfig, logout};
+use signed_request::{ContractSignatures, UsedRequests};
+use siwe::{Siwe, SiweConfig, SiweSessions};
+use state::{AppState, GamePhase, RoundInfo};
+use stats::{my_stats, player_stats};
+use table::table;
+use telemetry::{metrics, track_http};
+use ws::ws;
 
+pub mod actions;
+pub mod admin;
+pub mod api;
+pub mod api_keys;
+pub mod auth;
 pub mod bindings;
 pub mod cards;
+pub mod codec;
+pub mod db;
+pub mod equity;
+pub mod events;
+pub mod health;
+pub mod history;
 pub mod listener;
+pub mod openapi;
+pub mod policy;
 pub mod privy;
+pub mod ranking;
+pub mod rounds;
+pub mod sealed;
+pub mod sessions;
+pub mod signed_request;
+pub mod siwe;
 pub mod state;
+pub mod stats;
+pub mod table;
+pub mod telemetry;
+pub mod ws;
+
+/// Default maximum number of seconds to wait for the pending dealer transactions and HTTP
+/// connections on shutdown.
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 60;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -44,10 +97,63 @@ async fn main() -> Result<()> {
         .with(env_filter)
         .init();
 
+    // `pokerd-backend mint-token <wallet>` prints a token for the dev auth provider
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("mint-token") {
+        let wallet = args
+            .next()
+            .context("usage: pokerd-backend mint-token <wallet>")?
+            .parse()?;
+        println!(
+            "{}",
+            DevAuth::from_env()?.mint(wallet, DEFAULT_DEV_TOKEN_TTL)?
+        );
+        return Ok(());
+    }
+
+    // init metrics
+    let metrics_handle = telemetry::install()?;
+    tokio::spawn(telemetry::run_upkeep(metrics_handle.clone()));
+
+    let shutdown_timeout = Duration::from_secs(
+        env::var("SHUTDOWN_TIMEOUT_SECS")
+            .ok()
+            .map(|timeout| timeout.parse())
+            .transpose()
+            .context("SHUTDOWN_TIMEOUT_SECS environment variable")?
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+    );
+    let shutdown = CancellationToken::new();
+
     // init app state
+    let siwe = SiweConfig::from_env()?.map(Siwe::new);
+    let mut auth = auth::provider_from_env()?;
+    if let Some(siwe) = &siwe {
+        // the sessions issued after a Sign-In With Ethereum are accepted next to the other tokens
+        auth = Arc::new(SiweSessions::new(siwe.clone(), auth));
+    }
+    let store = Store::open(env::var("DB_PATH").unwrap_or(DEFAULT_DB_PATH.to_string()))
+        .context("opening database")?;
+    let sessions = SessionRegistry::new(
+        SessionsConfig::from_env()?,
+        store
+            .revoked_sessions()
+            .context("loading revoked sessions")?,
+    );
+    let api_keys = ApiKeys::new(store.api_keys().context("loading API keys")?);
+    let rpc_url = env::var("RPC_URL").context("RPC_URL environment variable")?;
+    let delegated_wallets = actions::wallets_from_env(&rpc_url)?;
+    let (admin_commands, commands) = mpsc::channel(ADMIN_COMMANDS_CAPACITY);
     let state = Arc::new(RwLock::new(AppState {
-        privy: Privy::new(PrivyConfig::from_env()?),
-        rpc_url: env::var("RPC_URL").context("RPC_URL environment variable")?,
+        auth,
+        siwe,
+        sessions,
+        policy: PolicyConfig::from_env()?,
+        delegated_wallets,
+        signed_requests: UsedRequests::default(),
+        contract_signatures: ContractSignatures::new(&rpc_url)?,
+        api_keys,
+        rpc_url,
         signer: PrivateKeySigner::from_bytes(&B256::from_hex(
             env::var("PRIVATE_KEY").context("PRIVATE_KEY environment variable")?,
         )?)?
@@ -56,38 +162,189 @@ async fn main() -> Result<()> {
             .context("TABLE_ADDRESS environment variable")?
             .parse()?,
         table_players: vec![],
+        buy_ins: HashMap::new(),
         phase: GamePhase::default(),
+        round: RoundInfo::default(),
+        rounds: RoundHistory::new(
+            env::var("ROUND_HISTORY_SIZE")
+                .ok()
+                .map(|size| size.parse())
+                .transpose()
+                .context("ROUND_HISTORY_SIZE environment variable")?
+                .unwrap_or(DEFAULT_ROUND_HISTORY_SIZE),
+        ),
+        store,
         last_processed_block: 0,
+        events: broadcast::channel(EVENTS_CAPACITY).0,
+        admin: AdminConfig::from_env()?,
+        admin_commands,
+        auto_start: true,
+        metrics: metrics_handle,
+        phase_started_at: Instant::now(),
+        listener: ListenerStatus::default(),
+        readiness: ReadinessConfig::from_env()?,
+        shutdown: shutdown.clone(),
     }));
 
+    // resume the round which was ongoing when we were last shut down
+    {
+        let mut state = state.write().unwrap();
+        let table_address = state.table_address;
+        if let Some(saved) = state
+            .store
+            .take_dealer_state(table_address)
+            .context("loading saved state")?
+        {
+            match state.restore_state(saved) {
+                Ok(()) => info!(
+                    phase = state.phase.name(),
+                    last_processed_block = state.last_processed_block,
+                    "restored saved state"
+                ),
+                Err(err) => warn!(?err, "discarding saved state"),
+            }
+        }
+    }
+
     // start listener task
-    let listener_handle = tokio::spawn({
+    let mut listener_handle = tokio::spawn({
         let state = Arc::clone(&state);
-        listener::listen(state)
+        listener::listen(state, commands, shutdown.clone())
     });
 
     // routes
-    let app = Router::new()
-        .route("/", get(healthcheck))
+    let api = Router::new()
         .route("/hand", get(hand))
+        .route("/hand/equity", get(hand_equity))
+        .route("/hand/key", put(register_hand_key).delete(delete_hand_key))
         .route("/flop", get(flop))
         .route("/turn", get(turn))
         .route("/river", get(river))
-        .with_state(state);
+        .route("/table", get(table))
+        .route("/rounds/latest", get(latest_round))
+        .route("/rounds/{id}", get(round))
+        .route("/history", get(history))
+        .route("/players/{address}/stats", get(player_stats))
+        .route("/me/stats", get(my_stats))
+        .route("/ws", get(ws))
+        .route("/auth/siwe/nonce", get(siwe::nonce))
+        .route("/auth/siwe/verify", post(siwe::verify))
+        .route("/auth/logout", post(logout))
+        .route("/api-keys", post(create_api_key))
+        .route("/api-keys/{id}/revoke", post(revoke_api_key))
+        .route("/actions/bet", post(actions::bet))
+        .route("/actions/fold", post(actions::fold))
+        .route("/actions/leave", post(actions::leave))
+        .route("/admin/phase", get(show_phase))
+        .route("/admin/round/cancel", post(cancel_round))
+        .route("/admin/round/timeout", post(timeout_player))
+        .route("/admin/auto-start/pause", post(pause_auto_start))
+        .route("/admin/auto-start/resume", post(resume_auto_start))
+        .route("/admin/resync", post(resync))
+        .route("/admin/sessions", get(list_sessions))
+        .route("/admin/sessions/revoke", post(revoke_sessions));
+    let app = Router::new()
+        .route("/", get(healthcheck))
+        .route("/live", get(live))
+        .route("/ready", get(ready))
+        .route("/openapi.json", get(openapi))
+        .route("/metrics", get(metrics))
+        .nest(API_VERSION, api.clone())
+        // unversioned routes are kept for existing clients
+        .merge(api)
+        .fallback(not_found)
+        .layer(middleware::from_fn(track_http))
+        .with_state(Arc::clone(&state));
 
     // start server
     let port = env::var("PORT").unwrap_or("8080".to_string());
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}")).await?;
     debug!("serving on port {port}");
-    tokio::select! {
-        res = listener_handle => {
+    let drain = CancellationToken::new();
+    let mut server_handle = tokio::spawn(
+        axum::serve(listener, app)
+            .with_graceful_shutdown(drain.clone().cancelled_owned())
+            .into_future(),
+    );
+    let stopped_listener = tokio::select! {
+        res = &mut listener_handle => Some(res),
+        res = &mut server_handle => {
             res??;
-        }
-        _ = axum::serve(listener, app) => {
             warn!("server stopped");
+            return Ok(());
+        }
+        () = shutdown_signal() => None,
+    };
+
+    // graceful shutdown
+    info!(timeout = ?shutdown_timeout, "shutting down");
+    let deadline = tokio::time::Instant::now() + shutdown_timeout;
+    let listener_res = match stopped_listener {
+        Some(res) => Ok(res),
+        None => {
+            // the listener might still process a batch of logs, which must not start a new round
+            state.write().unwrap().auto_start = false;
+            shutdown.cancel();
+            tokio::time::timeout_at(deadline, listener_handle).await
+        }
+    };
+    let listener_res = listener_res.map(|res| res.map_err(anyhow::Error::from).and_then(|res| res));
+    let listener_res = match listener_res {
+        Ok(Ok(())) => {
+            let state = state.read().unwrap();
+            match state.store.save_dealer_state(&state.save_state()) {
+                Ok(()) => info!(
+                    last_processed_block = state.last_processed_block,
+                    "state saved"
+                ),
+                Err(err) => error!(?err, "failed to save state"),
+            }
+            Ok(())
         }
+        // the state might be halfway through the logs of a block, replaying them on top of it would apply some of
+        // them twice, so nothing is saved and the next start syncs from the chain, cancelling an ongoing round
+        Ok(Err(err)) => {
+            error!(?err, "listener failed, state not saved");
+            Err(err)
+        }
+        // the state might not match the chain, it is synced again on the next start
+        Err(_) => {
+            warn!(
+                pending_txs = pending_txs(),
+                "listener did not stop in time, state not saved"
+            );
+            Ok(())
+        }
+    };
+    drain.cancel();
+    match tokio::time::timeout_at(deadline, server_handle).await {
+        Ok(res) => res??,
+        Err(_) => warn!("HTTP connections not drained in time"),
+    }
+    info!("shut down");
+    listener_res
+}
+
+/// Resolves when the process receives Ctrl+C or SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Ctrl+C handler should be installed");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("SIGTERM handler should be installed")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
     }
-    Ok(())
 }
 
 #[instrument]
@@ -103,27 +360,88 @@ pub enum AppError {
     Internal(#[from] anyhow::Error),
 
     #[error("auth error: {0}")]
-    Auth(#[from] privy::PrivyError),
+    Auth(#[from] auth::AuthError),
 
     #[error("cards endpoint error: {0}")]
     Cards(#[from] cards::CardsError),
+
+    #[error("rounds endpoint error: {0}")]
+    Rounds(#[from] rounds::RoundsError),
+
+    #[error("history endpoint error: {0}")]
+    History(#[from] history::HistoryError),
+
+    #[error("admin endpoint error: {0}")]
+    Admin(#[from] admin::AdminError),
+
+    #[error("sign-in error: {0}")]
+    Siwe(#[from] siwe::SiweError),
+
+    #[error("signed request error: {0}")]
+    SignedRequest(#[from] signed_request::SignedRequestError),
+
+    #[error("action error: {0}")]
+    Action(#[from] actions::ActionError),
+
+    #[error("API key error: {0}")]
+    ApiKey(#[from] api_keys::ApiKeyError),
+
+    #[error("sealed cards error: {0}")]
+    Sealed(#[from] sealed::SealedError),
+}
+
+impl ApiError for AppError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Auth(err) => err.status(),
+            AppError::Cards(err) => err.status(),
+            AppError::Rounds(err) => err.status(),
+            AppError::History(err) => err.status(),
+            AppError::Admin(err) => err.status(),
+            AppError::Siwe(err) => err.status(),
+            AppError::SignedRequest(err) => err.status(),
+            AppError::Action(err) => err.status(),
+            AppError::ApiKey(err) => err.status(),
+            AppError::Sealed(err) => err.status(),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::Internal(_) => "internal_error",
+            AppError::Auth(err) => err.code(),
+            AppError::Cards(err) => err.code(),
+            AppError::Rounds(err) => err.code(),
+            AppError::History(err) => err.code(),
+            AppError::Admin(err) => err.code(),
+            AppError::Siwe(err) => err.code(),
+            AppError::SignedRequest(err) => err.code(),
+            AppError::Action(err) => err.code(),
+            AppError::ApiKey(err) => err.code(),
+            AppError::Sealed(err) => err.code(),
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            AppError::Internal(_) => None,
+            AppError::Auth(err) => err.details(),
+            AppError::Cards(err) => err.details(),
+            AppError::Rounds(err) => err.details(),
+            AppError::History(err) => err.details(),
+            AppError::Admin(err) => err.details(),
+            AppError::Siwe(err) => err.details(),
+            AppError::SignedRequest(err) => err.details(),
+            AppError::Action(err) => err.details(),
+            AppError::ApiKey(err) => err.details(),
+            AppError::Sealed(err) => err.details(),
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            AppError::Internal(error) => (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
-            AppError::Auth(err) => {
-                return err.into_response();
-            }
-            AppError::Cards(err) => {
-                return err.into_response();
-            }
-        };
-
-        let body = Json(json!({
-            "error": error_message
-        }));
-        (status, body).into_response()
+        error_response(&self)
     }
 }
@@ -0,0 +1,133 @@
+use axum::Json;
+use tracing::{info, instrument};
+use utoipa::{
+    Modify, OpenApi, ToSchema,
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+/// Name of the security scheme used by authenticated endpoints.
+pub const BEARER_AUTH: &str = "bearer";
+
+/// Name of the security scheme of the API keys of the bots.
+pub const API_KEY_AUTH: &str = "api_key";
+
+/// JSON representation of a card, as serialized by `rs_poker`.
+#[derive(ToSchema)]
+#[schema(as = Card)]
+#[allow(dead_code)] // only used to generate the schema
+pub struct CardSchema {
+    value: CardValue,
+    suit: CardSuit,
+}
+
+/// JSON representation of a set of cards, as serialized by `rs_poker`.
+#[derive(ToSchema)]
+#[schema(as = Hand)]
+#[allow(dead_code)] // only used to generate the schema
+pub struct HandSchema(Vec<CardSchema>);
+
+#[derive(ToSchema)]
+#[allow(dead_code)] // only used to generate the schema
+pub enum CardValue {
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+    Ace,
+}
+
+#[derive(ToSchema)]
+#[allow(dead_code)] // only used to generate the schema
+pub enum CardSuit {
+    Spade,
+    Club,
+    Heart,
+    Diamond,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "pokerd-backend", description = "Backend service for pokerd"),
+    servers((url = "/v1")),
+    paths(
+        crate::health::live,
+        crate::health::ready,
+        crate::cards::hand,
+        crate::cards::hand_equity,
+        crate::sealed::register_hand_key,
+        crate::sealed::delete_hand_key,
+        crate::cards::flop,
+        crate::cards::turn,
+        crate::cards::river,
+        crate::table::table,
+        crate::rounds::latest_round,
+        crate::rounds::round,
+        crate::history::history,
+        crate::stats::player_stats,
+        crate::stats::my_stats,
+        crate::ws::ws,
+        crate::siwe::nonce,
+        crate::siwe::verify,
+        crate::sessions::logout,
+        crate::api_keys::create_api_key,
+        crate::api_keys::revoke_api_key,
+        crate::actions::bet,
+        crate::actions::fold,
+        crate::actions::leave,
+        crate::admin::show_phase,
+        crate::admin::cancel_round,
+        crate::admin::timeout_player,
+        crate::admin::pause_auto_start,
+        crate::admin::resume_auto_start,
+        crate::admin::resync,
+        crate::admin::list_sessions,
+        crate::admin::revoke_sessions,
+    ),
+    components(schemas(crate::sealed::SealedCards)),
+    modifiers(&BearerAuth),
+)]
+pub struct ApiDoc;
+
+/// Registers the bearer token scheme expected by [`crate::auth::UserSession`], and the API keys
+/// accepted by [`crate::signed_request::Requester`].
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            BEARER_AUTH,
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some(
+                        "Users with several linked wallets can select which one to act as with the \
+                         `x-pokerd-wallet` header",
+                    ))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            API_KEY_AUTH,
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                crate::api_keys::API_KEY_HEADER,
+                "API key created with `POST /api-keys`, only accepted on the endpoints of its scopes",
+            ))),
+        );
+    }
+}
+
+#[instrument]
+pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    info!("endpoint called");
+    Json(ApiDoc::openapi())
+}
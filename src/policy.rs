@@ -0,0 +1,155 @@
+use std::env;
+
+use alloy::primitives::U256;
+use anyhow::{Context as _, Result};
+use axum::{http::StatusCode, response::IntoResponse};
+use serde_json::json;
+
+use crate::{
+    api::{ApiError, error_response},
+    privy::{LinkedAccount, User},
+};
+
+/// The attributes of a user which the eligibility policy is checked against.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserAttributes {
+    pub has_accepted_terms: bool,
+    pub is_guest: bool,
+    pub has_mfa: bool,
+    pub has_verified_email: bool,
+}
+
+impl From<&User> for UserAttributes {
+    fn from(user: &User) -> Self {
+        Self {
+            has_accepted_terms: user.has_accepted_terms,
+            is_guest: user.is_guest,
+            has_mfa: !user.mfa_methods.is_empty(),
+            has_verified_email: user.linked_accounts.iter().any(
+                |account| matches!(account, LinkedAccount::Email(email) if email.verified_at > 0),
+            ),
+        }
+    }
+}
+
+/// Who can play at the table, checked when a player authenticates.
+///
+/// Only the Privy sessions carry the user attributes, so the other sessions, the signed requests
+/// and the API keys are rejected as soon as a rule is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct PolicyConfig {
+    pub require_accepted_terms: bool,
+    pub reject_guests: bool,
+    pub require_verified_email: bool,
+
+    /// Buy-in from which the player must have enabled MFA, in wei
+    pub mfa_min_stake: Option<U256>,
+}
+
+impl PolicyConfig {
+    pub fn from_env() -> Result<Self> {
+        let flag = |name: &'static str| -> Result<bool> {
+            Ok(env::var(name)
+                .ok()
+                .map(|flag| flag.parse())
+                .transpose()
+                .with_context(|| format!("{name} environment variable"))?
+                .unwrap_or_default())
+        };
+        Ok(Self {
+            require_accepted_terms: flag("POLICY_REQUIRE_ACCEPTED_TERMS")?,
+            reject_guests: flag("POLICY_REJECT_GUESTS")?,
+            require_verified_email: flag("POLICY_REQUIRE_VERIFIED_EMAIL")?,
+            mfa_min_stake: env::var("POLICY_MFA_MIN_STAKE")
+                .ok()
+                .filter(|stake| !stake.is_empty())
+                .map(|stake| stake.parse())
+                .transpose()
+                .context("POLICY_MFA_MIN_STAKE environment variable")?,
+        })
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.require_accepted_terms
+            || self.reject_guests
+            || self.require_verified_email
+            || self.mfa_min_stake.is_some()
+    }
+
+    /// Check that a user is eligible to play with the given stake at the table.
+    pub fn check(
+        &self,
+        attributes: Option<&UserAttributes>,
+        stake: U256,
+    ) -> Result<(), PolicyError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let attributes = attributes.ok_or(PolicyError::AttributesUnavailable)?;
+        if self.require_accepted_terms && !attributes.has_accepted_terms {
+            return Err(PolicyError::TermsNotAccepted);
+        }
+        if self.reject_guests && attributes.is_guest {
+            return Err(PolicyError::GuestNotAllowed);
+        }
+        if self.require_verified_email && !attributes.has_verified_email {
+            return Err(PolicyError::EmailNotVerified);
+        }
+        match self.mfa_min_stake {
+            Some(threshold) if stake >= threshold && !attributes.has_mfa => {
+                Err(PolicyError::MfaRequired { stake, threshold })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum PolicyError {
+    #[error("the provider of the session doesn't report the user attributes required by the table")]
+    AttributesUnavailable,
+
+    #[error("the terms of the table were not accepted")]
+    TermsNotAccepted,
+
+    #[error("guest accounts are not allowed at the table")]
+    GuestNotAllowed,
+
+    #[error("a verified email is required at the table")]
+    EmailNotVerified,
+
+    #[error("MFA is required for a stake of {stake} wei, from {threshold} wei")]
+    MfaRequired { stake: U256, threshold: U256 },
+}
+
+impl ApiError for PolicyError {
+    fn status(&self) -> StatusCode {
+        StatusCode::FORBIDDEN
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            PolicyError::AttributesUnavailable => "policy_attributes_unavailable",
+            PolicyError::TermsNotAccepted => "terms_not_accepted",
+            PolicyError::GuestNotAllowed => "guest_not_allowed",
+            PolicyError::EmailNotVerified => "email_not_verified",
+            PolicyError::MfaRequired { .. } => "mfa_required",
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            PolicyError::MfaRequired { stake, threshold } => {
+                Some(json!({ "stake": stake, "threshold": threshold }))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for PolicyError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
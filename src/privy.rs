@@ -1,34 +1,39 @@
 use std::{
+    collections::HashMap,
     fmt,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
-use alloy::primitives::Address;
-use anyhow::{Result, anyhow};
-use axum::{
-    Json, RequestPartsExt as _, extract::FromRequestParts, http::request::Parts,
-    response::IntoResponse,
-};
-use axum_extra::{
-    TypedHeader,
-    headers::{Authorization, authorization::Bearer},
-};
+use alloy::primitives::{Address, B256, Bytes};
+use anyhow::{Context as _, Result, anyhow};
+use axum::response::IntoResponse;
 use base64::{Engine as _, engine::general_purpose::STANDARD};
+use futures_util::future::BoxFuture;
 use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode};
+use metrics::{counter, histogram};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use tracing::debug;
 
-use crate::state::AppState;
+use crate::{
+    actions::{ActionError, DelegatedTx, DelegatedWallets},
+    api::{ApiError, error_response},
+    auth::{AuthError, AuthProvider, UserSession},
+    policy::UserAttributes,
+    telemetry::{PRIVY_CACHE_HITS, PRIVY_LOOKUP_DURATION, PRIVY_LOOKUP_FAILURES},
+};
+
+/// Default number of seconds for which the wallet of a user session is cached.
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 300;
 
 #[derive(thiserror::Error, Debug)]
 pub enum PrivyError {
     #[error("missing environment variable: {0}")]
     MissingEnv(&'static str),
 
-    #[error("invalid or missing token")]
-    InvalidToken,
+    #[error("invalid environment variable: {0:#}")]
+    InvalidEnv(anyhow::Error),
 
     #[error("failed to validate access token: {0}")]
     ValidateAccessTokenError(jsonwebtoken::errors::Error),
@@ -47,24 +52,51 @@ pub enum PrivyError {
 
     #[error("failed to read decoding key: {0}")]
     ReadDecodingKeyError(jsonwebtoken::errors::Error),
+
+    #[error("failed to send wallet RPC request: {0}")]
+    WalletRpcRequestError(reqwest::Error),
+
+    #[error("wallet RPC request failed: {0}")]
+    WalletRpcFailed(anyhow::Error),
 }
 
-impl IntoResponse for PrivyError {
-    fn into_response(self) -> axum::response::Response {
-        let status = match self {
+impl ApiError for PrivyError {
+    fn status(&self) -> StatusCode {
+        match self {
             PrivyError::MissingEnv(_)
-            | PrivyError::GetUserByIdRequestError(_)
-            | PrivyError::ParseUserError(_)
+            | PrivyError::InvalidEnv(_)
             | PrivyError::ReadDecodingKeyError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            PrivyError::InvalidToken
-            | PrivyError::ValidateAccessTokenError(_)
+            PrivyError::ValidateAccessTokenError(_) => StatusCode::UNAUTHORIZED,
+            PrivyError::GetUserByIdRequestError(_)
             | PrivyError::GetUserByIdFailed(_)
-            | PrivyError::FindWalletError(_) => StatusCode::BAD_REQUEST,
-        };
-        let body = Json(json!({
-            "error": self.to_string(),
-        }));
-        (status, body).into_response()
+            | PrivyError::ParseUserError(_) => StatusCode::BAD_GATEWAY,
+            PrivyError::FindWalletError(_) => StatusCode::FORBIDDEN,
+            PrivyError::WalletRpcRequestError(_) | PrivyError::WalletRpcFailed(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            PrivyError::MissingEnv(_)
+            | PrivyError::InvalidEnv(_)
+            | PrivyError::ReadDecodingKeyError(_) => "auth_misconfigured",
+            PrivyError::ValidateAccessTokenError(_) => "invalid_token",
+            PrivyError::GetUserByIdRequestError(_)
+            | PrivyError::GetUserByIdFailed(_)
+            | PrivyError::ParseUserError(_) => "auth_upstream_error",
+            PrivyError::FindWalletError(_) => "wallet_not_found",
+            PrivyError::WalletRpcRequestError(_) | PrivyError::WalletRpcFailed(_) => {
+                "wallet_rpc_error"
+            }
+        }
+    }
+}
+
+impl IntoResponse for PrivyError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
     }
 }
 
@@ -73,12 +105,16 @@ pub struct PrivyConfig {
     pub app_id: String,
     pub app_secret: String,
     pub verification_key: String,
+
+    /// How long the wallet of a user session is cached before it is looked up again
+    pub cache_ttl: Duration,
 }
 
 impl fmt::Debug for PrivyConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("PrivyConfig")
             .field("app_id", &self.app_id)
+            .field("cache_ttl", &self.cache_ttl)
             .finish_non_exhaustive()
     }
 }
@@ -94,10 +130,19 @@ impl PrivyConfig {
         let verification_key = std::env::var("PRIVY_VERIFICATION_KEY")
             .map_err(|_| PrivyError::MissingEnv("PRIVY_VERIFICATION_KEY"))?;
 
+        let cache_ttl = std::env::var("PRIVY_CACHE_TTL_SECS")
+            .ok()
+            .map(|ttl| ttl.parse())
+            .transpose()
+            .context("PRIVY_CACHE_TTL_SECS environment variable")
+            .map_err(PrivyError::InvalidEnv)?
+            .unwrap_or(DEFAULT_CACHE_TTL_SECS);
+
         Ok(Self {
             app_id,
             app_secret,
             verification_key,
+            cache_ttl: Duration::from_secs(cache_ttl),
         })
     }
 }
@@ -107,15 +152,39 @@ pub fn base64encode(data: &[u8]) -> String {
     STANDARD.encode(data)
 }
 
+/// Wallets and attributes of the user sessions, keyed by user ID and session ID.
+type SessionCache = HashMap<(String, String), CachedUser>;
+
 #[derive(Debug, Clone)]
+struct CachedUser {
+    wallets: Vec<Address>,
+    attributes: UserAttributes,
+    delegated: HashMap<Address, String>,
+    fetched_at: Instant,
+}
+
+#[derive(Clone)]
 pub struct Privy {
     pub config: PrivyConfig,
     pub client: reqwest::Client,
+
+    /// The verification key, parsed once
+    key: Arc<DecodingKey>,
+    cache: Arc<Mutex<SessionCache>>,
+}
+
+impl fmt::Debug for Privy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Privy")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Privy {
-    #[must_use]
-    pub fn new(config: PrivyConfig) -> Self {
+    pub fn new(config: PrivyConfig) -> Result<Self, PrivyError> {
+        let key = DecodingKey::from_ec_pem(config.verification_key.as_bytes())
+            .map_err(PrivyError::ReadDecodingKeyError)?;
         let client = reqwest::Client::builder()
             .default_headers({
                 let mut headers = reqwest::header::HeaderMap::new();
@@ -140,26 +209,67 @@ impl Privy {
             })
             .build()
             .expect("reqwest client should build successfully");
-        Self { config, client }
+        Ok(Self {
+            config,
+            client,
+            key: Arc::new(key),
+            cache: Arc::default(),
+        })
     }
 
     pub async fn authenticate_user(&self, access_token: &str) -> Result<UserSession, PrivyError> {
         let claims = self.validate_access_token(access_token)?;
         debug!(?claims, "access token validated");
-        let user = self.get_user_by_id(&claims.user_id).await?;
+        if let Some(cached) = self.cached_user(&claims) {
+            counter!(PRIVY_CACHE_HITS).increment(1);
+            return Ok(
+                UserSession::new(claims.user_id, claims.session_id, cached.wallets)
+                    .with_attributes(cached.attributes)
+                    .with_delegated(cached.delegated),
+            );
+        }
+        let started = Instant::now();
+        let user = self.get_user_by_id(&claims.user_id).await;
+        histogram!(PRIVY_LOOKUP_DURATION).record(started.elapsed().as_secs_f64());
+        let user = user.inspect_err(|_| counter!(PRIVY_LOOKUP_FAILURES).increment(1))?;
         debug!(?user, "user found");
 
-        let evm_wallet =
-            find_wallet(&user.linked_accounts, "ethereum").map_err(PrivyError::FindWalletError)?;
-        let wallet = Address::parse_checksummed(&evm_wallet.address, None)
+        let linked = find_wallets(&user.linked_accounts, "ethereum");
+        let wallets = linked
+            .iter()
+            .map(|wallet| Address::parse_checksummed(&wallet.address, None))
+            .collect::<Result<Vec<_>, _>>()
             .map_err(|err| PrivyError::FindWalletError(err.into()))?;
-        debug!(user = user.id, ?wallet, "retrieved wallet for user");
-
-        Ok(UserSession {
-            user_id: user.id,
-            session_id: claims.session_id,
-            wallet,
-        })
+        // the wallets were parsed in the same order
+        let delegated: HashMap<Address, String> = linked
+            .iter()
+            .zip(&wallets)
+            .filter(|(wallet, _)| wallet.delegated)
+            .filter_map(|(wallet, address)| Some((*address, wallet.id.clone()?)))
+            .collect();
+        if wallets.is_empty() {
+            return Err(PrivyError::FindWalletError(anyhow!(
+                "could not find an ethereum wallet"
+            )));
+        }
+        debug!(user = user.id, ?wallets, "retrieved wallets for user");
+        let attributes = UserAttributes::from(&user);
+        self.cache
+            .lock()
+            .expect("cache lock should not be poisoned")
+            .insert(
+                (user.id.clone(), claims.session_id.clone()),
+                CachedUser {
+                    wallets: wallets.clone(),
+                    attributes: attributes.clone(),
+                    delegated: delegated.clone(),
+                    fetched_at: Instant::now(),
+                },
+            );
+
+        Ok(UserSession::new(user.id, claims.session_id, wallets)
+            .with_attributes(attributes)
+            .with_delegated(delegated))
     }
 
     pub fn validate_access_token(&self, access_token: &str) -> Result<PrivyClaims, PrivyError> {
@@ -167,15 +277,32 @@ impl Privy {
         validation.set_issuer(&["privy.io"]);
         validation.set_audience(&[&self.config.app_id]);
 
-        let key = DecodingKey::from_ec_pem(self.config.verification_key.as_bytes())
-            .map_err(PrivyError::ReadDecodingKeyError)?;
-
-        let token_data = decode::<PrivyClaims>(access_token, &key, &validation)
+        let token_data = decode::<PrivyClaims>(access_token, &self.key, &validation)
             .map_err(PrivyError::ValidateAccessTokenError)?;
 
         Ok(token_data.claims)
     }
 
+    fn cached_user(&self, claims: &PrivyClaims) -> Option<CachedUser> {
+        let cache = self
+            .cache
+            .lock()
+            .expect("cache lock should not be poisoned");
+        cache
+            .get(&(claims.user_id.clone(), claims.session_id.clone()))
+            .filter(|cached| cached.fetched_at.elapsed() < self.config.cache_ttl)
+            .cloned()
+    }
+
+    /// Drop the cached users which expired.
+    pub fn sweep_cache(&self) {
+        let ttl = self.config.cache_ttl;
+        self.cache
+            .lock()
+            .expect("cache lock should not be poisoned")
+            .retain(|_, cached| cached.fetched_at.elapsed() < ttl);
+    }
+
     pub async fn get_user_by_id(&self, user_id: &str) -> Result<User, PrivyError> {
         let url = format!("https://auth.privy.io/api/v1/users/{user_id}");
 
@@ -197,34 +324,65 @@ impl Privy {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct UserSession {
-    pub user_id: String,
-    pub session_id: String,
-    pub wallet: Address,
+impl AuthProvider for Privy {
+    fn authenticate<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<UserSession, AuthError>> {
+        Box::pin(async move { Ok(self.authenticate_user(token).await?) })
+    }
 }
 
-impl FromRequestParts<Arc<RwLock<AppState>>> for UserSession {
-    type Rejection = PrivyError;
+impl DelegatedWallets for Privy {
+    fn send<'a>(
+        &'a self,
+        session: &'a UserSession,
+        wallet: Address,
+        tx: DelegatedTx,
+    ) -> BoxFuture<'a, Result<B256, ActionError>> {
+        Box::pin(async move {
+            let wallet_id = session
+                .delegated
+                .get(&wallet)
+                .ok_or(ActionError::NotDelegated(wallet))?;
+            let url = format!("https://api.privy.io/v1/wallets/{wallet_id}/rpc");
+            let request = WalletRpcRequest {
+                method: "eth_sendTransaction",
+                caip2: format!("eip155:{}", tx.chain_id),
+                params: WalletRpcParams {
+                    transaction: WalletRpcTransaction {
+                        to: tx.to,
+                        data: tx.data,
+                    },
+                },
+            };
+
+            let response = self
+                .client
+                .post(url)
+                .json(&request)
+                .send()
+                .await
+                .map_err(PrivyError::WalletRpcRequestError)?;
+            if !response.status().is_success() {
+                return Err(PrivyError::WalletRpcFailed(anyhow!(
+                    "failed to send transaction: {}",
+                    response.status()
+                ))
+                .into());
+            }
+            let response: WalletRpcResponse = response
+                .json()
+                .await
+                .map_err(PrivyError::WalletRpcRequestError)?;
+            Ok(response.data.hash)
+        })
+    }
+}
 
-    async fn from_request_parts(
-        parts: &mut Parts,
-        state: &Arc<RwLock<AppState>>,
-    ) -> Result<Self, Self::Rejection> {
-        // Extract the token from the authorization header
-        let TypedHeader(Authorization(bearer)) = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| PrivyError::InvalidToken)?;
-        // validate token
-        let privy = {
-            state
-                .read()
-                .expect("lock should not be poisoned")
-                .privy
-                .clone() // this is relatively cheap to clone
-        };
-        privy.authenticate_user(bearer.token()).await
+/// Periodically drop the expired entries of the session cache, which are otherwise only ignored.
+pub async fn run_cache_sweep(privy: Privy) {
+    let mut interval = tokio::time::interval(privy.config.cache_ttl.max(Duration::from_secs(1)));
+    loop {
+        interval.tick().await;
+        privy.sweep_cache();
     }
 }
 
@@ -244,6 +402,35 @@ pub struct PrivyClaims {
     pub(crate) session_id: String,
 }
 
+/// Request to the RPC endpoint of the Privy server wallets.
+#[derive(Debug, Serialize)]
+struct WalletRpcRequest {
+    method: &'static str,
+    caip2: String,
+    params: WalletRpcParams,
+}
+
+#[derive(Debug, Serialize)]
+struct WalletRpcParams {
+    transaction: WalletRpcTransaction,
+}
+
+#[derive(Debug, Serialize)]
+struct WalletRpcTransaction {
+    to: Address,
+    data: Bytes,
+}
+
+#[derive(Debug, Deserialize)]
+struct WalletRpcResponse {
+    data: WalletRpcResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct WalletRpcResult {
+    hash: B256,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
     pub created_at: i64,
@@ -301,21 +488,18 @@ pub struct WalletAccount {
     pub id: Option<String>,
 }
 
-fn find_wallet<'a>(
+/// All the linked wallets of a chain type, in the order they were linked.
+fn find_wallets<'a>(
     linked_accounts: &'a [LinkedAccount],
     chain_type: &str,
-) -> Result<&'a WalletAccount> {
+) -> Vec<&'a WalletAccount> {
     linked_accounts
         .iter()
-        .find_map(|account| match account {
-            LinkedAccount::Wallet(wallet) => {
-                if wallet.chain_type == chain_type {
-                    Some(wallet.as_ref())
-                } else {
-                    None
-                }
+        .filter_map(|account| match account {
+            LinkedAccount::Wallet(wallet) if wallet.chain_type == chain_type => {
+                Some(wallet.as_ref())
             }
             _ => None,
         })
-        .ok_or_else(|| anyhow!("could not find a delegated {} wallet", chain_type))
+        .collect()
 }
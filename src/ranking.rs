@@ -0,0 +1,197 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use itertools::Itertools as _;
+use rs_poker::core::{Card, Hand, Rank, Rankable as _, Value};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::openapi::CardSchema;
+
+/// Detailed ranking of a hand, explaining how it compares to others.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct HandRanking {
+    /// The category of the hand, e.g. `two_pair`
+    pub rank: String,
+
+    /// The five cards making the best hand, the ones defining the category first
+    #[serde(default)]
+    #[schema(value_type = Vec<CardSchema>)]
+    pub best_five: Vec<Card>,
+
+    /// The cards of the best five which only serve to break ties
+    #[serde(default)]
+    #[schema(value_type = Vec<CardSchema>)]
+    pub kickers: Vec<Card>,
+}
+
+/// Human-readable category of a ranked hand.
+#[must_use]
+pub fn rank_name(rank: &Rank) -> &'static str {
+    match rank {
+        Rank::HighCard(_) => "high_card",
+        Rank::OnePair(_) => "one_pair",
+        Rank::TwoPair(_) => "two_pair",
+        Rank::ThreeOfAKind(_) => "three_of_a_kind",
+        Rank::Straight(_) => "straight",
+        Rank::Flush(_) => "flush",
+        Rank::FullHouse(_) => "full_house",
+        Rank::FourOfAKind(_) => "four_of_a_kind",
+        Rank::StraightFlush(_) => "straight_flush",
+    }
+}
+
+/// Rank the best five-card hand which can be made from the hole cards and the board.
+///
+/// Needs at least five cards in total.
+#[must_use]
+pub fn rank_hand(hole: &Hand, board: &[Card]) -> (Rank, HandRanking) {
+    let (rank, mut best_five) = hole
+        .iter()
+        .chain(board.iter().copied())
+        .combinations(5)
+        .map(|cards| (Hand::new_with_cards(cards.clone()).rank_five(), cards))
+        .max_by_key(|(rank, _)| *rank)
+        .expect("there should be at least five cards");
+
+    // group the cards by value, bigger groups first, then higher values first
+    let mut counts = HashMap::new();
+    for card in &best_five {
+        *counts.entry(card.value).or_insert(0usize) += 1;
+    }
+    best_five.sort_by_key(|c| (Reverse(counts[&c.value]), Reverse(c.value)));
+    if matches!(rank, Rank::Straight(_) | Rank::StraightFlush(_))
+        && best_five[0].value == Value::Ace
+        && best_five[1].value == Value::Five
+    {
+        // the ace plays low in the wheel
+        best_five.rotate_left(1);
+    }
+
+    let kickers = match rank {
+        Rank::HighCard(_) => best_five[1..].to_vec(),
+        Rank::OnePair(_) | Rank::TwoPair(_) | Rank::ThreeOfAKind(_) | Rank::FourOfAKind(_) => {
+            best_five
+                .iter()
+                .filter(|c| counts[&c.value] == 1)
+                .copied()
+                .collect()
+        }
+        // all five cards are part of the made hand
+        Rank::Straight(_) | Rank::Flush(_) | Rank::FullHouse(_) | Rank::StraightFlush(_) => {
+            vec![]
+        }
+    };
+    let ranking = HandRanking {
+        rank: rank_name(&rank).to_string(),
+        best_five,
+        kickers,
+    };
+    (rank, ranking)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rank(hole: &str, board: &str) -> (Rank, HandRanking) {
+        let board: Vec<Card> = Hand::new_from_str(board).unwrap().iter().collect();
+        rank_hand(&Hand::new_from_str(hole).unwrap(), &board)
+    }
+
+    fn values(cards: &[Card]) -> Vec<Value> {
+        cards.iter().map(|c| c.value).collect()
+    }
+
+    #[test]
+    fn best_five_of_seven() {
+        let (_, ranking) = rank("AhKh", "QhJhTh2c3d");
+        assert_eq!(ranking.rank, "straight_flush");
+        assert_eq!(
+            ranking.best_five,
+            Hand::new_from_str("AhKhQhJhTh")
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        );
+        assert!(ranking.kickers.is_empty());
+    }
+
+    #[test]
+    fn pair_kickers() {
+        let (_, ranking) = rank("AsKd", "Ah9c7d4s2h");
+        assert_eq!(ranking.rank, "one_pair");
+        assert_eq!(
+            values(&ranking.best_five),
+            [
+                Value::Ace,
+                Value::Ace,
+                Value::King,
+                Value::Nine,
+                Value::Seven
+            ]
+        );
+        assert_eq!(
+            values(&ranking.kickers),
+            [Value::King, Value::Nine, Value::Seven]
+        );
+
+        // the same pair, decided by the kickers
+        let (weaker, _) = rank("AcQd", "Ah9c7d4s2h");
+        assert!(rank("AsKd", "Ah9c7d4s2h").0 > weaker);
+    }
+
+    #[test]
+    fn two_pair_kicker() {
+        // three pairs, the third one can't play and the queen is the kicker
+        let (_, ranking) = rank("Qs7h", "AhAcKdKs7c");
+        assert_eq!(ranking.rank, "two_pair");
+        assert_eq!(
+            values(&ranking.best_five),
+            [
+                Value::Ace,
+                Value::Ace,
+                Value::King,
+                Value::King,
+                Value::Queen
+            ]
+        );
+        assert_eq!(values(&ranking.kickers), [Value::Queen]);
+    }
+
+    #[test]
+    fn trips_kickers() {
+        let (_, ranking) = rank("9s9d", "9cAhKd4s2h");
+        assert_eq!(ranking.rank, "three_of_a_kind");
+        assert_eq!(values(&ranking.kickers), [Value::Ace, Value::King]);
+    }
+
+    #[test]
+    fn board_plays() {
+        let (rank_a, ranking_a) = rank("2c3d", "AsAdKsKdQc");
+        let (rank_b, ranking_b) = rank("4h5h", "AsAdKsKdQc");
+        assert_eq!(rank_a, rank_b);
+        assert_eq!(ranking_a, ranking_b);
+        assert_eq!(values(&ranking_a.kickers), [Value::Queen]);
+    }
+
+    #[test]
+    fn wheel() {
+        let (wheel, ranking) = rank("As2d", "3c4h5s9dKc");
+        assert_eq!(ranking.rank, "straight");
+        assert_eq!(
+            values(&ranking.best_five),
+            [
+                Value::Five,
+                Value::Four,
+                Value::Three,
+                Value::Two,
+                Value::Ace
+            ]
+        );
+        assert!(ranking.kickers.is_empty());
+
+        // the wheel is the lowest straight
+        let (six_high, _) = rank("6c2d", "3c4h5s9dKc");
+        assert!(six_high > wheel);
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, RwLock},
+};
+
+use alloy::primitives::{Address, I256, U256};
+use axum::{Json, debug_handler, extract::State, http::StatusCode, response::IntoResponse};
+use rs_poker::core::{Card, Hand};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+
+use crate::{
+    api::{ApiError, ErrorBody, Path, error_response},
+    openapi::{CardSchema, HandSchema},
+    ranking::HandRanking,
+    state::{AppState, Bet, DealerTx, Seat},
+};
+
+/// Number of completed rounds kept in memory by default.
+pub const DEFAULT_ROUND_HISTORY_SIZE: usize = 20;
+
+/// A player who was dealt into a round.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoundPlayer {
+    #[schema(value_type = String)]
+    pub address: Address,
+    pub seat: Seat,
+
+    /// Whether the player folded or left before the end of the round
+    pub folded: bool,
+
+    /// The net gains of the player for this round, if known
+    #[schema(value_type = Option<String>)]
+    pub gains: Option<I256>,
+}
+
+/// A hand which was shown at showdown.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ShownHand {
+    #[schema(value_type = String)]
+    pub address: Address,
+    pub seat: Seat,
+    #[schema(value_type = HandSchema)]
+    pub cards: Hand,
+
+    #[serde(flatten)]
+    pub ranking: HandRanking,
+}
+
+/// The outcome of a completed round.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoundSummary {
+    /// The on-chain ID of the round, if it was known
+    #[schema(value_type = Option<String>)]
+    pub id: Option<U256>,
+
+    /// The address of the table contract
+    #[schema(value_type = String)]
+    pub table_address: Address,
+
+    /// Unix timestamp at which the cards were dealt, if it was known
+    pub started_at: Option<i64>,
+
+    /// Unix timestamp at which the end of the round was processed
+    pub ended_at: i64,
+
+    /// Whether the round went to showdown
+    pub showdown: bool,
+
+    /// The players which were dealt into the round
+    pub players: Vec<RoundPlayer>,
+
+    /// The community cards which were revealed
+    #[schema(value_type = Vec<CardSchema>)]
+    pub board: Vec<Card>,
+
+    /// The hands which were shown, empty if there was no showdown
+    pub hands: Vec<ShownHand>,
+
+    /// All the bets placed during the round, in order
+    pub bets: Vec<Bet>,
+
+    /// The seat(s) of the winner(s)
+    pub winners: Vec<Seat>,
+
+    /// The pot, as reported by the contract
+    #[schema(value_type = String)]
+    pub pot: U256,
+
+    /// The transactions sent by the dealer during the round
+    pub dealer_txs: Vec<DealerTx>,
+}
+
+/// The last completed rounds, most recent last.
+#[derive(Debug, Clone)]
+pub struct RoundHistory {
+    capacity: usize,
+    rounds: VecDeque<RoundSummary>,
+}
+
+impl RoundHistory {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            rounds: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, round: RoundSummary) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.rounds.len() == self.capacity {
+            self.rounds.pop_front();
+        }
+        self.rounds.push_back(round);
+    }
+
+    #[must_use]
+    pub fn latest(&self) -> Option<&RoundSummary> {
+        self.rounds.back()
+    }
+
+    #[must_use]
+    pub fn get(&self, id: U256) -> Option<&RoundSummary> {
+        self.rounds.iter().rev().find(|r| r.id == Some(id))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/rounds/latest",
+    tag = "rounds",
+    responses(
+        (status = 200, description = "The last completed round", body = RoundSummary),
+        (status = 404, description = "No round has completed yet", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn latest_round(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<RoundSummary>, RoundsError> {
+    info!("endpoint called");
+    let state = state.read().expect("state lock should not be poisoned");
+    let Some(round) = state.rounds.latest() else {
+        return Err(RoundsError::NoRounds);
+    };
+    let round = round.clone();
+    drop(state);
+    Ok(Json(round))
+}
+
+#[utoipa::path(
+    get,
+    path = "/rounds/{id}",
+    tag = "rounds",
+    params(("id" = u64, Path, description = "The on-chain ID of the round")),
+    responses(
+        (status = 200, description = "A recently completed round", body = RoundSummary),
+        (status = 404, description = "The round is not among the recently completed ones", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn round(
+    Path(id): Path<u64>,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<RoundSummary>, RoundsError> {
+    info!("endpoint called");
+    let state = state.read().expect("state lock should not be poisoned");
+    let Some(round) = state.rounds.get(U256::from(id)) else {
+        return Err(RoundsError::RoundNotFound(id));
+    };
+    let round = round.clone();
+    drop(state);
+    Ok(Json(round))
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum RoundsError {
+    #[error("no round has completed yet")]
+    NoRounds,
+
+    #[error("round not found: {0}")]
+    RoundNotFound(u64),
+}
+
+impl ApiError for RoundsError {
+    fn status(&self) -> StatusCode {
+        StatusCode::NOT_FOUND
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            RoundsError::NoRounds => "no_rounds",
+            RoundsError::RoundNotFound(_) => "round_not_found",
+        }
+    }
+}
+
+impl IntoResponse for RoundsError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
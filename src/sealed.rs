@@ -0,0 +1,301 @@
+use std::sync::{Arc, RwLock};
+
+use axum::{debug_handler, extract::State, http::StatusCode, response::IntoResponse};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit as _, aead::Aead as _};
+use hkdf::Hkdf;
+use rs_poker::core::Card;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::{debug, info, instrument};
+use utoipa::ToSchema;
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+use crate::{
+    api::{ApiError, ErrorBody, JsonBody, error_response},
+    auth::UserSession,
+    codec::{self, CardFormat},
+    state::AppState,
+};
+
+/// Name of the scheme with which the cards are sealed, part of the API contract.
+pub const SEALING_ALGORITHM: &str = "x25519-hkdf-sha256-chacha20poly1305";
+
+/// HKDF info of the sealed cards, to change along with [`SEALING_ALGORITHM`].
+const HKDF_INFO: &[u8] = b"pokerd sealed cards v1";
+
+/// An X25519 public key registered by a session to receive its hole cards encrypted.
+#[derive(Debug, Clone, Copy)]
+pub struct HandKey(PublicKey);
+
+impl HandKey {
+    /// Parse a base64 encoded X25519 public key, rejecting the low order points.
+    pub fn parse(encoded: &str) -> Result<Self, SealedError> {
+        let bytes: [u8; 32] = STANDARD
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(SealedError::InvalidKey)?;
+        let key = PublicKey::from(bytes);
+        if !EphemeralSecret::random()
+            .diffie_hellman(&key)
+            .was_contributory()
+        {
+            return Err(SealedError::InvalidKey);
+        }
+        Ok(Self(key))
+    }
+
+    /// Encrypt cards to the key, as the JSON of their encoding in the given notation.
+    ///
+    /// All the sealed cards go through here, so that the plaintext is the same whether they are sent on the
+    /// websocket or returned by `GET /hand`.
+    pub fn seal_cards(
+        &self,
+        cards: impl IntoIterator<Item = Card>,
+        format: CardFormat,
+    ) -> Result<SealedCards, SealedError> {
+        self.seal(codec::encode_cards(cards, format).to_string().as_bytes())
+    }
+
+    /// Encrypt a message to the key, with a new ephemeral key pair.
+    ///
+    /// The ChaCha20-Poly1305 key and nonce are derived with HKDF-SHA256 from the shared secret,
+    /// salted with the ephemeral public key followed by the recipient public key.
+    fn seal(&self, plaintext: &[u8]) -> Result<SealedCards, SealedError> {
+        let ephemeral = EphemeralSecret::random();
+        let ephemeral_public = PublicKey::from(&ephemeral);
+        let shared = ephemeral.diffie_hellman(&self.0);
+        let salt = [
+            ephemeral_public.as_bytes().as_slice(),
+            self.0.as_bytes().as_slice(),
+        ]
+        .concat();
+        let mut okm = [0u8; 44];
+        Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+            .expand(HKDF_INFO, &mut okm)
+            .map_err(|_| SealedError::Encryption)?;
+        let (key, nonce) = okm.split_at(32);
+        let ciphertext = ChaCha20Poly1305::new(key.into())
+            .encrypt(nonce.into(), plaintext)
+            .map_err(|_| SealedError::Encryption)?;
+        Ok(SealedCards {
+            algorithm: SEALING_ALGORITHM,
+            ephemeral_public_key: STANDARD.encode(ephemeral_public.as_bytes()),
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+}
+
+/// Cards encrypted to the hand key of the session, only readable by the client.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SealedCards {
+    /// The sealing scheme, `x25519-hkdf-sha256-chacha20poly1305`
+    pub algorithm: &'static str,
+
+    /// The base64 encoded X25519 public key of the sender, to derive the shared secret with
+    pub ephemeral_public_key: String,
+
+    /// The base64 encoded ciphertext and tag of the JSON encoded cards, as `GET /hand` returns them in the
+    /// requested notation, e.g. `[{"value":"Ace","suit":"Spade"},{"value":"King","suit":"Diamond"}]`. The hole
+    /// cards sent on the websocket are in the default `json` notation
+    pub ciphertext: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RegisterHandKey {
+    /// The base64 encoded X25519 public key, ideally generated for the session
+    pub public_key: String,
+}
+
+#[utoipa::path(
+    put,
+    path = "/hand/key",
+    tag = "cards",
+    security(("bearer" = [])),
+    request_body = RegisterHandKey,
+    responses(
+        (status = 204, description = "The hole cards are now sent to the session encrypted to the key"),
+        (status = 400, description = "The key is not a valid X25519 public key", body = ErrorBody),
+        (status = 401, description = "Missing or invalid access token", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn register_hand_key(
+    session: UserSession,
+    State(state): State<Arc<RwLock<AppState>>>,
+    JsonBody(request): JsonBody<RegisterHandKey>,
+) -> Result<StatusCode, SealedError> {
+    info!("endpoint called");
+    let key = HandKey::parse(&request.public_key)?;
+    state
+        .read()
+        .expect("state lock should not be poisoned")
+        .sessions
+        .set_hand_key(&session.session_id, Some(key));
+    debug!(session = session.session_id, "registered hand key");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/hand/key",
+    tag = "cards",
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "The hole cards are sent to the session in plaintext again"),
+        (status = 401, description = "Missing or invalid access token", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn delete_hand_key(
+    session: UserSession,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> StatusCode {
+    info!("endpoint called");
+    state
+        .read()
+        .expect("state lock should not be poisoned")
+        .sessions
+        .set_hand_key(&session.session_id, None);
+    StatusCode::NO_CONTENT
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum SealedError {
+    #[error("invalid X25519 public key")]
+    InvalidKey,
+
+    #[error("failed to encrypt the cards")]
+    Encryption,
+}
+
+impl ApiError for SealedError {
+    fn status(&self) -> StatusCode {
+        match self {
+            SealedError::InvalidKey => StatusCode::BAD_REQUEST,
+            SealedError::Encryption => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            SealedError::InvalidKey => "invalid_hand_key",
+            SealedError::Encryption => "encryption_failed",
+        }
+    }
+}
+
+impl IntoResponse for SealedError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Open sealed cards as a client would, with the secret of the hand key.
+    fn open(
+        secret: EphemeralSecret,
+        public: &PublicKey,
+        sealed: &SealedCards,
+    ) -> Result<Vec<u8>, chacha20poly1305::Error> {
+        let ephemeral: [u8; 32] = STANDARD
+            .decode(&sealed.ephemeral_public_key)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let ephemeral = PublicKey::from(ephemeral);
+        let shared = secret.diffie_hellman(&ephemeral);
+        let salt = [
+            ephemeral.as_bytes().as_slice(),
+            public.as_bytes().as_slice(),
+        ]
+        .concat();
+        let mut okm = [0u8; 44];
+        Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+            .expand(HKDF_INFO, &mut okm)
+            .unwrap();
+        let (key, nonce) = okm.split_at(32);
+        ChaCha20Poly1305::new(key.into()).decrypt(
+            nonce.into(),
+            STANDARD.decode(&sealed.ciphertext).unwrap().as_slice(),
+        )
+    }
+
+    fn hand_key() -> (EphemeralSecret, PublicKey, HandKey) {
+        let secret = EphemeralSecret::random();
+        let public = PublicKey::from(&secret);
+        let key = HandKey::parse(&STANDARD.encode(public.as_bytes())).unwrap();
+        (secret, public, key)
+    }
+
+    #[test]
+    fn seal_then_open() {
+        let (secret, public, key) = hand_key();
+        let sealed = key.seal(br#"{"seat":1,"cards":"AsKd"}"#).unwrap();
+        assert_eq!(sealed.algorithm, SEALING_ALGORITHM);
+        assert_eq!(
+            open(secret, &public, &sealed).unwrap(),
+            br#"{"seat":1,"cards":"AsKd"}"#
+        );
+    }
+
+    #[test]
+    fn sealed_cards_are_encoded() {
+        let cards: Vec<Card> = rs_poker::core::Hand::new_from_str("AsKd")
+            .unwrap()
+            .iter()
+            .collect();
+        for format in [CardFormat::Json, CardFormat::Compact] {
+            let (secret, public, key) = hand_key();
+            let sealed = key.seal_cards(cards.iter().copied(), format).unwrap();
+            let opened: serde_json::Value =
+                serde_json::from_slice(&open(secret, &public, &sealed).unwrap()).unwrap();
+            assert_eq!(opened, codec::encode_cards(cards.iter().copied(), format));
+        }
+    }
+
+    #[test]
+    fn sealing_twice_uses_new_ephemeral_keys() {
+        let (_, _, key) = hand_key();
+        let first = key.seal(b"cards").unwrap();
+        let second = key.seal(b"cards").unwrap();
+        assert_ne!(first.ephemeral_public_key, second.ephemeral_public_key);
+        assert_ne!(first.ciphertext, second.ciphertext);
+    }
+
+    #[test]
+    fn only_opened_with_the_hand_key() {
+        let (_, public, key) = hand_key();
+        let sealed = key.seal(b"cards").unwrap();
+        assert!(open(EphemeralSecret::random(), &public, &sealed).is_err());
+
+        let (secret, public, key) = hand_key();
+        let mut sealed = key.seal(b"cards").unwrap();
+        let mut ciphertext = STANDARD.decode(&sealed.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        sealed.ciphertext = STANDARD.encode(ciphertext);
+        assert!(open(secret, &public, &sealed).is_err());
+    }
+
+    #[test]
+    fn invalid_keys() {
+        for key in [
+            "",
+            "not base64",
+            STANDARD.encode([1u8; 31]).as_str(),
+            STANDARD.encode([0u8; 32]).as_str(),
+        ] {
+            assert!(
+                matches!(HandKey::parse(key), Err(SealedError::InvalidKey)),
+                "{key}"
+            );
+        }
+    }
+}
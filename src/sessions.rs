@@ -0,0 +1,438 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use alloy::primitives::Address;
+use anyhow::{Context as _, Result};
+use axum::{debug_handler, extract::State, http::StatusCode};
+use chrono::Utc;
+use rusqlite::params;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument};
+use utoipa::ToSchema;
+
+use crate::{
+    api::ErrorBody,
+    auth::{AuthError, UserSession},
+    db::{Store, StoreError},
+    sealed::HandKey,
+    state::AppState,
+};
+
+/// Number of seconds after which a session which made no request is forgotten.
+const SESSION_IDLE_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// Table of the revoked sessions.
+pub(crate) const SESSIONS_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS revoked_sessions (
+    session_id TEXT PRIMARY KEY,
+    revoked_at INTEGER NOT NULL,
+    revoked_by TEXT NOT NULL
+);
+";
+
+#[derive(Debug, Clone, Default)]
+pub struct SessionsConfig {
+    /// Whether a wallet seated at the table can only be used by one session at a time
+    pub single_session_per_wallet: bool,
+}
+
+impl SessionsConfig {
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            single_session_per_wallet: env::var("SINGLE_SESSION_PER_WALLET")
+                .ok()
+                .map(|single| single.parse())
+                .transpose()
+                .context("SINGLE_SESSION_PER_WALLET environment variable")?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// A session which made requests to the backend.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ActiveSession {
+    pub user_id: String,
+    pub session_id: String,
+    #[schema(value_type = Vec<String>)]
+    pub wallets: Vec<Address>,
+
+    /// Unix timestamp of the first request made with the session
+    pub first_seen: i64,
+
+    /// Unix timestamp of the last request made with the session
+    pub last_seen: i64,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// The active sessions, by session ID
+    active: HashMap<String, ActiveSession>,
+    revoked: HashSet<String>,
+
+    /// The session currently holding each seated wallet, with one session per wallet
+    holders: HashMap<Address, String>,
+
+    /// The keys which the sessions registered to receive their hole cards encrypted
+    hand_keys: HashMap<String, HandKey>,
+
+    /// Cancelled when a session is revoked or superseded, with the number of connections watching
+    /// it, by session ID
+    watched: HashMap<String, (CancellationToken, usize)>,
+}
+
+impl Registry {
+    /// Notify the connections of a session that it can't be used anymore.
+    fn close(&mut self, session_id: &str) {
+        if let Some((closed, _)) = self.watched.remove(session_id) {
+            closed.cancel();
+        }
+    }
+}
+
+/// Tracks the active sessions, and rejects the revoked ones.
+#[derive(Debug, Clone)]
+pub struct SessionRegistry {
+    pub config: SessionsConfig,
+    inner: Arc<Mutex<Registry>>,
+}
+
+impl SessionRegistry {
+    /// A registry rejecting the sessions which were revoked before a restart.
+    #[must_use]
+    pub fn new(config: SessionsConfig, revoked: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            config,
+            inner: Arc::new(Mutex::new(Registry {
+                revoked: revoked.into_iter().collect(),
+                ..Registry::default()
+            })),
+        }
+    }
+
+    /// Record a request made with a session, rejecting it if the session was revoked.
+    ///
+    /// With one session per wallet, the newest session using a seated wallet takes it over, and
+    /// the older sessions are rejected for as long as it is seated.
+    pub fn touch(
+        &self,
+        session: &UserSession,
+        seated: impl Fn(Address) -> bool,
+    ) -> Result<(), AuthError> {
+        let now = Utc::now().timestamp();
+        let mut registry = self
+            .inner
+            .lock()
+            .expect("sessions lock should not be poisoned");
+        if registry.revoked.contains(&session.session_id) {
+            return Err(AuthError::SessionRevoked);
+        }
+        let is_new = !registry.active.contains_key(&session.session_id);
+        if self.config.single_session_per_wallet {
+            let seated: Vec<Address> = session
+                .wallets
+                .iter()
+                .copied()
+                .filter(|wallet| seated(*wallet))
+                .collect();
+            let superseded = seated.iter().copied().find(|wallet| {
+                !is_new
+                    && registry
+                        .holders
+                        .get(wallet)
+                        .is_some_and(|holder| *holder != session.session_id)
+            });
+            if let Some(wallet) = superseded {
+                return Err(AuthError::SessionSuperseded(wallet));
+            }
+            for wallet in seated {
+                let previous = registry.holders.insert(wallet, session.session_id.clone());
+                if let Some(previous) = previous.filter(|holder| *holder != session.session_id) {
+                    registry.close(&previous);
+                }
+            }
+        }
+        if is_new {
+            registry
+                .active
+                .retain(|_, active| now - active.last_seen < SESSION_IDLE_TTL_SECS);
+            let Registry {
+                active,
+                holders,
+                hand_keys,
+                ..
+            } = &mut *registry;
+            holders
+                .retain(|_, holder| active.contains_key(holder) || *holder == session.session_id);
+            hand_keys.retain(|session_id, _| active.contains_key(session_id));
+        }
+        registry
+            .active
+            .entry(session.session_id.clone())
+            .and_modify(|active| active.last_seen = now)
+            .or_insert_with(|| ActiveSession {
+                user_id: session.user_id.clone(),
+                session_id: session.session_id.clone(),
+                wallets: session.wallets.clone(),
+                first_seen: now,
+                last_seen: now,
+            });
+        Ok(())
+    }
+
+    /// The active sessions, most recently used first.
+    #[must_use]
+    pub fn list(&self) -> Vec<ActiveSession> {
+        let registry = self
+            .inner
+            .lock()
+            .expect("sessions lock should not be poisoned");
+        let mut sessions: Vec<_> = registry.active.values().cloned().collect();
+        sessions.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+        sessions
+    }
+
+    /// Register the key to encrypt the hole cards of a session to, or remove it.
+    pub fn set_hand_key(&self, session_id: &str, key: Option<HandKey>) {
+        let mut registry = self
+            .inner
+            .lock()
+            .expect("sessions lock should not be poisoned");
+        match key {
+            Some(key) => registry.hand_keys.insert(session_id.to_string(), key),
+            None => registry.hand_keys.remove(session_id),
+        };
+    }
+
+    /// The key to encrypt the hole cards of a session to, if it registered one.
+    #[must_use]
+    pub fn hand_key(&self, session_id: &str) -> Option<HandKey> {
+        self.inner
+            .lock()
+            .expect("sessions lock should not be poisoned")
+            .hand_keys
+            .get(session_id)
+            .copied()
+    }
+
+    /// Watch a session from a long-lived connection, returning a token cancelled once the session is
+    /// revoked or superseded by another session.
+    ///
+    /// The connection must call [`SessionRegistry::unwatch`] when it closes.
+    #[must_use]
+    pub fn watch(&self, session_id: &str) -> CancellationToken {
+        let mut registry = self
+            .inner
+            .lock()
+            .expect("sessions lock should not be poisoned");
+        if registry.revoked.contains(session_id) {
+            let closed = CancellationToken::new();
+            closed.cancel();
+            return closed;
+        }
+        let (closed, watchers) = registry
+            .watched
+            .entry(session_id.to_string())
+            .or_insert_with(|| (CancellationToken::new(), 0));
+        *watchers += 1;
+        closed.clone()
+    }
+
+    /// Stop watching a session, once a connection which called [`SessionRegistry::watch`] closed.
+    pub fn unwatch(&self, session_id: &str, closed: &CancellationToken) {
+        if closed.is_cancelled() {
+            // the session was already closed, and is maybe watched again by newer connections
+            return;
+        }
+        let mut registry = self
+            .inner
+            .lock()
+            .expect("sessions lock should not be poisoned");
+        if let Some((_, watchers)) = registry.watched.get_mut(session_id) {
+            *watchers -= 1;
+            if *watchers == 0 {
+                registry.watched.remove(session_id);
+            }
+        }
+    }
+
+    /// Revoke a session, or all the active sessions of a wallet, returning the revoked session IDs.
+    pub fn revoke(&self, session_id: Option<&str>, wallet: Option<Address>) -> Vec<String> {
+        let mut registry = self
+            .inner
+            .lock()
+            .expect("sessions lock should not be poisoned");
+        let mut revoked: Vec<String> = session_id.map(ToString::to_string).into_iter().collect();
+        if let Some(wallet) = wallet {
+            revoked.extend(
+                registry
+                    .active
+                    .values()
+                    .filter(|active| active.wallets.contains(&wallet))
+                    .map(|active| active.session_id.clone()),
+            );
+        }
+        revoked.sort();
+        revoked.dedup();
+        for session_id in &revoked {
+            registry.active.remove(session_id);
+            registry.holders.retain(|_, holder| holder != session_id);
+            registry.hand_keys.remove(session_id);
+            registry.revoked.insert(session_id.clone());
+            registry.close(session_id);
+        }
+        revoked
+    }
+}
+
+impl Store {
+    /// Record revoked sessions.
+    pub fn record_revocations(
+        &self,
+        session_ids: &[String],
+        revoked_at: i64,
+        revoked_by: &str,
+    ) -> Result<(), StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        for session_id in session_ids {
+            tx.execute(
+                "INSERT OR IGNORE INTO revoked_sessions (session_id, revoked_at, revoked_by) VALUES (?1, ?2, ?3)",
+                params![session_id, revoked_at, revoked_by],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The IDs of all the revoked sessions.
+    pub fn revoked_sessions(&self) -> Result<Vec<String>, StoreError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT session_id FROM revoked_sessions")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(ids)
+    }
+}
+
+/// Persist revoked sessions, so that they stay revoked after a restart.
+pub async fn persist_revocations(
+    state: &Arc<RwLock<AppState>>,
+    session_ids: Vec<String>,
+    revoked_by: String,
+) {
+    let store = state
+        .read()
+        .expect("lock should not be poisoned")
+        .store
+        .clone();
+    match tokio::task::spawn_blocking(move || {
+        store.record_revocations(&session_ids, Utc::now().timestamp(), &revoked_by)
+    })
+    .await
+    {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => error!(?err, "failed to persist revoked sessions"),
+        Err(err) => error!(?err, "database task failed"),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "auth",
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "The session was revoked, its token is not accepted anymore"),
+        (status = 401, description = "Missing or invalid access token", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn logout(
+    session: UserSession,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> StatusCode {
+    info!("endpoint called");
+    let revoked = {
+        state
+            .read()
+            .expect("state lock should not be poisoned")
+            .sessions
+            .revoke(Some(&session.session_id), None)
+    };
+    info!(user = session.user_id, "logged out");
+    persist_revocations(&state, revoked, session.user_id).await;
+    StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(session_id: &str, wallet: Address) -> UserSession {
+        UserSession::new(
+            format!("user:{session_id}"),
+            session_id.to_string(),
+            vec![wallet],
+        )
+    }
+
+    #[test]
+    fn revoking_closes_watchers() {
+        let sessions = SessionRegistry::new(SessionsConfig::default(), []);
+        let wallet = Address::repeat_byte(1);
+        sessions.touch(&session("a", wallet), |_| false).unwrap();
+        let closed = sessions.watch("a");
+        let other = sessions.watch("b");
+        assert_eq!(sessions.revoke(None, Some(wallet)), vec!["a".to_string()]);
+        assert!(closed.is_cancelled());
+        assert!(!other.is_cancelled());
+        // watching after the revocation, e.g. racing with it
+        assert!(sessions.watch("a").is_cancelled());
+    }
+
+    #[test]
+    fn superseding_closes_watchers() {
+        let sessions = SessionRegistry::new(
+            SessionsConfig {
+                single_session_per_wallet: true,
+            },
+            [],
+        );
+        let wallet = Address::repeat_byte(1);
+        sessions.touch(&session("a", wallet), |_| true).unwrap();
+        let closed = sessions.watch("a");
+        sessions.touch(&session("b", wallet), |_| true).unwrap();
+        assert!(closed.is_cancelled());
+        assert!(matches!(
+            sessions.touch(&session("a", wallet), |_| true),
+            Err(AuthError::SessionSuperseded(_))
+        ));
+    }
+
+    #[test]
+    fn closed_watchers_dont_unwatch_newer_ones() {
+        let sessions = SessionRegistry::new(
+            SessionsConfig {
+                single_session_per_wallet: true,
+            },
+            [],
+        );
+        let wallet = Address::repeat_byte(1);
+        sessions.touch(&session("a", wallet), |_| true).unwrap();
+        let old = sessions.watch("a");
+        sessions.touch(&session("b", wallet), |_| true).unwrap();
+        // the wallet left the table, so the first session can be used again
+        sessions.touch(&session("a", wallet), |_| false).unwrap();
+        let new = sessions.watch("a");
+        sessions.unwatch("a", &old);
+        sessions.revoke(Some("a"), None);
+        assert!(new.is_cancelled());
+    }
+}
@@ -0,0 +1,501 @@
+use std::{
+    collections::HashMap,
+    fmt, slice,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    hex,
+    primitives::{Address, B256, Bytes, FixedBytes, PrimitiveSignature, U256},
+    providers::{Provider as _, RootProvider},
+    sol,
+    sol_types::{SolStruct as _, eip712_domain},
+};
+use anyhow::Result;
+use axum::{
+    extract::FromRequestParts,
+    http::{HeaderMap, StatusCode, request::Parts},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use serde_json::json;
+use tracing::{debug, warn};
+
+use crate::{
+    api::{ApiError, error_response},
+    api_keys::{API_KEY_HEADER, ApiKey, ApiKeyError, ApiKeyScope},
+    auth::{AuthError, UserSession},
+    bindings::IERC1271,
+    policy::{PolicyConfig, PolicyError},
+    state::{AppState, Seat},
+};
+
+/// Name of the EIP-712 domain of the signed requests, whose verifying contract is the table.
+const DOMAIN_NAME: &str = "pokerd";
+const DOMAIN_VERSION: &str = "1";
+
+/// Maximum number of seconds a signed request can be valid for, which bounds the replay set.
+pub const MAX_VALIDITY_SECS: u64 = 5 * 60;
+
+/// Value returned by `isValidSignature` for a valid signature (EIP-1271).
+const EIP1271_MAGIC_VALUE: FixedBytes<4> = FixedBytes([0x16, 0x26, 0xba, 0x7e]);
+
+/// How long whether a wallet has code is remembered.
+const CODE_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+pub const ROUND_ID_HEADER: &str = "x-pokerd-round-id";
+pub const SEAT_HEADER: &str = "x-pokerd-seat";
+pub const EXPIRY_HEADER: &str = "x-pokerd-expiry";
+pub const SIGNATURE_HEADER: &str = "x-pokerd-signature";
+
+sol! {
+    /// Request for the hole cards of a seat, signed by the wallet of the player with EIP-712.
+    #[derive(Debug)]
+    struct HandRequest {
+        address table;
+        uint256 roundId;
+        uint256 seat;
+        uint64 expiry;
+    }
+}
+
+/// The signed requests which were already used, with their expiry, so that they can't be replayed.
+#[derive(Debug, Clone, Default)]
+pub struct UsedRequests(Arc<Mutex<HashMap<B256, u64>>>);
+
+impl UsedRequests {
+    /// Mark a request as used, returning `false` if it already was.
+    fn insert(&self, hash: B256, expiry: u64, now: u64) -> bool {
+        let mut used = self.0.lock().expect("replay lock should not be poisoned");
+        used.retain(|_, expiry| *expiry > now);
+        used.insert(hash, expiry).is_none()
+    }
+}
+
+/// The wallet making a request, proven either by a bearer token, by a signed [`HandRequest`] or by
+/// an API key.
+#[derive(Debug, Clone)]
+pub enum Requester {
+    Session(UserSession),
+    Signed(Address),
+    ApiKey(ApiKey),
+}
+
+impl Requester {
+    /// The signing wallet or the wallet of the API key, or else the wallet of the session picked
+    /// with [`UserSession::wallet_for`].
+    pub fn wallet_for(&self, matches: impl Fn(Address) -> bool) -> Address {
+        match self {
+            Requester::Session(session) => session.wallet_for(matches),
+            Requester::Signed(wallet) => *wallet,
+            Requester::ApiKey(key) => key.wallet,
+        }
+    }
+
+    /// Check that an API key is allowed on an endpoint, the other requesters always being allowed.
+    pub fn require(&self, scope: ApiKeyScope) -> Result<(), ApiKeyError> {
+        match self {
+            Requester::ApiKey(key) if !key.scopes.contains(&scope) => {
+                Err(ApiKeyError::MissingScope(scope))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the requester is eligible to play at the table, given the stake of its wallets.
+    ///
+    /// Only the sessions can carry the user attributes, so the signed requests and the API keys are
+    /// rejected as soon as a rule of the policy is enabled.
+    pub fn check_policy(
+        &self,
+        policy: &PolicyConfig,
+        stake_of: impl FnOnce(&[Address]) -> U256,
+    ) -> Result<(), PolicyError> {
+        let (attributes, wallets) = match self {
+            Requester::Session(session) => {
+                (session.attributes.as_ref(), session.wallets.as_slice())
+            }
+            Requester::Signed(wallet) => (None, slice::from_ref(wallet)),
+            Requester::ApiKey(key) => (None, slice::from_ref(&key.wallet)),
+        };
+        policy.check(attributes, stake_of(wallets))
+    }
+}
+
+impl FromRequestParts<Arc<RwLock<AppState>>> for Requester {
+    type Rejection = SignedRequestError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<RwLock<AppState>>,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(secret) = parts.headers.get(API_KEY_HEADER) {
+            let secret = secret.to_str().map_err(|_| ApiKeyError::InvalidKey)?;
+            let keys = state
+                .read()
+                .expect("state lock should not be poisoned")
+                .api_keys
+                .clone();
+            let key = keys.authenticate(secret)?;
+            debug!(id = key.id, wallet = ?key.wallet, "API key verified");
+            let requester = Requester::ApiKey(key);
+            {
+                let state = state.read().expect("state lock should not be poisoned");
+                requester.check_policy(&state.policy, |wallets| state.stake_of(wallets))?;
+            }
+            return Ok(requester);
+        }
+        if !parts.headers.contains_key(SIGNATURE_HEADER) {
+            return Ok(Requester::Session(
+                UserSession::from_request_parts(parts, state).await?,
+            ));
+        }
+        let request = SignedHeaders::parse(&parts.headers)?;
+        let now = u64::try_from(Utc::now().timestamp()).unwrap_or_default();
+        if request.expiry <= now {
+            return Err(SignedRequestError::Expired);
+        }
+        if request.expiry > now + MAX_VALIDITY_SECS {
+            return Err(SignedRequestError::ExpiryTooFar);
+        }
+
+        // the seat must be dealt into the ongoing round
+        let (hash, wallet, seat, contract_signatures, used) = {
+            let state = state.read().expect("state lock should not be poisoned");
+            let chain_id = state
+                .listener
+                .chain_id
+                .ok_or(SignedRequestError::ChainUnknown)?;
+            if state.round.id != Some(request.round_id) {
+                return Err(SignedRequestError::RoundMismatch(state.round.id));
+            }
+            let seat = Seat::try_from(request.seat)
+                .map_err(|_| SignedRequestError::InvalidHeader(SEAT_HEADER))?;
+            let wallet = state
+                .get_players()
+                .and_then(|players| players.iter().find(|p| p.seat == seat))
+                .map(|p| p.address)
+                .ok_or(SignedRequestError::SeatNotInRound(seat))?;
+            let domain = eip712_domain! {
+                name: DOMAIN_NAME,
+                version: DOMAIN_VERSION,
+                chain_id: chain_id,
+                verifying_contract: state.table_address,
+            };
+            let hash = HandRequest {
+                table: state.table_address,
+                roundId: request.round_id,
+                seat: request.seat,
+                expiry: request.expiry,
+            }
+            .eip712_signing_hash(&domain);
+            (
+                hash,
+                wallet,
+                seat,
+                state.contract_signatures.clone(),
+                state.signed_requests.clone(),
+            )
+        };
+
+        let signed_by_eoa = PrimitiveSignature::try_from(request.signature.as_ref())
+            .ok()
+            .and_then(|signature| signature.recover_address_from_prehash(&hash).ok())
+            .is_some_and(|signer| signer == wallet);
+        if !signed_by_eoa
+            && !contract_signatures
+                .is_valid(wallet, hash, request.signature)
+                .await
+        {
+            return Err(SignedRequestError::InvalidSignature);
+        }
+        let requester = Requester::Signed(wallet);
+        {
+            let state = state.read().expect("state lock should not be poisoned");
+            requester.check_policy(&state.policy, |wallets| state.stake_of(wallets))?;
+        }
+        if !used.insert(hash, request.expiry, now) {
+            return Err(SignedRequestError::Replayed);
+        }
+        debug!(?wallet, %seat, "signed hand request verified");
+        Ok(requester)
+    }
+}
+
+/// Checks the signatures of smart contract wallets with EIP-1271, with a provider shared by all
+/// the requests.
+#[derive(Clone)]
+pub struct ContractSignatures {
+    provider: RootProvider,
+
+    /// Whether the wallets have code, with when it was looked up
+    has_code: Arc<Mutex<HashMap<Address, (bool, Instant)>>>,
+}
+
+impl fmt::Debug for ContractSignatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContractSignatures").finish_non_exhaustive()
+    }
+}
+
+impl ContractSignatures {
+    pub fn new(rpc_url: &str) -> Result<Self> {
+        Ok(Self {
+            provider: RootProvider::new_http(rpc_url.parse()?),
+            has_code: Arc::default(),
+        })
+    }
+
+    /// Check the signature of a smart contract wallet, the wallets without code (e.g. with a
+    /// signature which is not valid for their EOA) being rejected without calling them.
+    pub async fn is_valid(&self, wallet: Address, hash: B256, signature: Bytes) -> bool {
+        if !self.has_code(wallet).await {
+            return false;
+        }
+        match IERC1271::new(wallet, &self.provider)
+            .isValidSignature(hash, signature)
+            .call()
+            .await
+        {
+            Ok(result) => result.magicValue == EIP1271_MAGIC_VALUE,
+            Err(err) => {
+                warn!(?wallet, ?err, "EIP-1271 signature check failed");
+                false
+            }
+        }
+    }
+
+    /// Whether a wallet has code, looked up at most once per [`CODE_CACHE_TTL`].
+    async fn has_code(&self, wallet: Address) -> bool {
+        let cached = self
+            .has_code
+            .lock()
+            .expect("code cache lock should not be poisoned")
+            .get(&wallet)
+            .filter(|(_, looked_up_at)| looked_up_at.elapsed() < CODE_CACHE_TTL)
+            .map(|(has_code, _)| *has_code);
+        if let Some(has_code) = cached {
+            return has_code;
+        }
+        let has_code = match self.provider.get_code_at(wallet).await {
+            Ok(code) => !code.is_empty(),
+            Err(err) => {
+                // not cached, the node may just be unreachable
+                warn!(?wallet, ?err, "failed to look up the code of the wallet");
+                return false;
+            }
+        };
+        let mut cache = self
+            .has_code
+            .lock()
+            .expect("code cache lock should not be poisoned");
+        cache.retain(|_, (_, looked_up_at)| looked_up_at.elapsed() < CODE_CACHE_TTL);
+        cache.insert(wallet, (has_code, Instant::now()));
+        has_code
+    }
+}
+
+/// The fields of a [`HandRequest`] and its signature, as sent in the request headers.
+struct SignedHeaders {
+    round_id: U256,
+    seat: U256,
+    expiry: u64,
+    signature: Bytes,
+}
+
+impl SignedHeaders {
+    fn parse(headers: &HeaderMap) -> Result<Self, SignedRequestError> {
+        let header = |name: &'static str| {
+            headers
+                .get(name)
+                .ok_or(SignedRequestError::MissingHeader(name))?
+                .to_str()
+                .map_err(|_| SignedRequestError::InvalidHeader(name))
+        };
+        Ok(Self {
+            round_id: header(ROUND_ID_HEADER)?
+                .parse()
+                .map_err(|_| SignedRequestError::InvalidHeader(ROUND_ID_HEADER))?,
+            seat: header(SEAT_HEADER)?
+                .parse()
+                .map_err(|_| SignedRequestError::InvalidHeader(SEAT_HEADER))?,
+            expiry: header(EXPIRY_HEADER)?
+                .parse()
+                .map_err(|_| SignedRequestError::InvalidHeader(EXPIRY_HEADER))?,
+            signature: hex::decode(header(SIGNATURE_HEADER)?)
+                .map_err(|_| SignedRequestError::InvalidHeader(SIGNATURE_HEADER))?
+                .into(),
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum SignedRequestError {
+    #[error("missing header: {0}")]
+    MissingHeader(&'static str),
+
+    #[error("invalid header: {0}")]
+    InvalidHeader(&'static str),
+
+    #[error("signed request expired")]
+    Expired,
+
+    #[error("signed request expires in more than {MAX_VALIDITY_SECS} seconds")]
+    ExpiryTooFar,
+
+    #[error("chain of the table is not known yet")]
+    ChainUnknown,
+
+    #[error("signed request is not for the current round")]
+    RoundMismatch(Option<U256>),
+
+    #[error("seat is not dealt into the current round: {0}")]
+    SeatNotInRound(Seat),
+
+    #[error("invalid signature")]
+    InvalidSignature,
+
+    #[error("signed request was already used")]
+    Replayed,
+
+    #[error("auth error: {0}")]
+    Auth(#[from] AuthError),
+
+    #[error("API key error: {0}")]
+    ApiKey(#[from] ApiKeyError),
+
+    #[error("user is not eligible: {0}")]
+    Policy(#[from] PolicyError),
+}
+
+impl ApiError for SignedRequestError {
+    fn status(&self) -> StatusCode {
+        match self {
+            SignedRequestError::MissingHeader(_)
+            | SignedRequestError::InvalidHeader(_)
+            | SignedRequestError::ExpiryTooFar => StatusCode::BAD_REQUEST,
+            SignedRequestError::Expired
+            | SignedRequestError::InvalidSignature
+            | SignedRequestError::Replayed => StatusCode::UNAUTHORIZED,
+            SignedRequestError::ChainUnknown => StatusCode::SERVICE_UNAVAILABLE,
+            SignedRequestError::RoundMismatch(_) => StatusCode::CONFLICT,
+            SignedRequestError::SeatNotInRound(_) => StatusCode::NOT_FOUND,
+            SignedRequestError::Auth(err) => err.status(),
+            SignedRequestError::ApiKey(err) => err.status(),
+            SignedRequestError::Policy(err) => err.status(),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            SignedRequestError::MissingHeader(_)
+            | SignedRequestError::InvalidHeader(_)
+            | SignedRequestError::ExpiryTooFar => "invalid_signed_request",
+            SignedRequestError::Expired => "signed_request_expired",
+            SignedRequestError::InvalidSignature => "invalid_signature",
+            SignedRequestError::Replayed => "signed_request_replayed",
+            SignedRequestError::ChainUnknown => "chain_unknown",
+            SignedRequestError::RoundMismatch(_) => "round_mismatch",
+            SignedRequestError::SeatNotInRound(_) => "seat_not_in_round",
+            SignedRequestError::Auth(err) => err.code(),
+            SignedRequestError::ApiKey(err) => err.code(),
+            SignedRequestError::Policy(err) => err.code(),
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            SignedRequestError::MissingHeader(header)
+            | SignedRequestError::InvalidHeader(header) => Some(json!({ "header": header })),
+            SignedRequestError::RoundMismatch(current) => Some(json!({ "current_round": current })),
+            SignedRequestError::SeatNotInRound(seat) => Some(json!({ "seat": seat })),
+            SignedRequestError::Auth(err) => err.details(),
+            SignedRequestError::ApiKey(err) => err.details(),
+            SignedRequestError::Policy(err) => err.details(),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for SignedRequestError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::policy::UserAttributes;
+
+    use super::*;
+
+    fn requesters() -> Vec<Requester> {
+        let wallet = Address::repeat_byte(1);
+        let session = UserSession::new("user".to_string(), "session".to_string(), vec![wallet]);
+        vec![
+            Requester::Session(session.clone()),
+            Requester::Session(session.with_attributes(UserAttributes {
+                has_accepted_terms: false,
+                ..UserAttributes::default()
+            })),
+            Requester::Signed(wallet),
+            Requester::ApiKey(ApiKey {
+                id: "key".to_string(),
+                wallet,
+                scopes: vec![ApiKeyScope::Hand],
+                rate_limit: 60,
+                created_at: 0,
+                expires_at: None,
+                revoked_at: None,
+            }),
+        ]
+    }
+
+    #[test]
+    fn policy_rejects_every_requester() {
+        let policy = PolicyConfig {
+            require_accepted_terms: true,
+            ..PolicyConfig::default()
+        };
+        for requester in requesters() {
+            let err = SignedRequestError::from(
+                requester.check_policy(&policy, |_| U256::ZERO).unwrap_err(),
+            );
+            assert_eq!(err.status(), StatusCode::FORBIDDEN, "{requester:?}");
+        }
+    }
+
+    #[test]
+    fn policy_checks_the_stake_of_the_wallet() {
+        let policy = PolicyConfig {
+            mfa_min_stake: Some(U256::from(100)),
+            ..PolicyConfig::default()
+        };
+        let session = UserSession::new(
+            "user".to_string(),
+            "session".to_string(),
+            vec![Address::repeat_byte(1)],
+        )
+        .with_attributes(UserAttributes::default());
+        let requester = Requester::Session(session);
+        requester.check_policy(&policy, |_| U256::from(99)).unwrap();
+        assert!(matches!(
+            requester.check_policy(&policy, |wallets| {
+                assert_eq!(wallets, [Address::repeat_byte(1)]);
+                U256::from(100)
+            }),
+            Err(PolicyError::MfaRequired { .. })
+        ));
+    }
+
+    #[test]
+    fn disabled_policy_accepts_every_requester() {
+        for requester in requesters() {
+            requester
+                .check_policy(&PolicyConfig::default(), |_| U256::MAX)
+                .unwrap();
+        }
+    }
+}
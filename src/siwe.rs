@@ -0,0 +1,701 @@
+use std::{
+    collections::HashMap,
+    env, fmt,
+    str::FromStr,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    hex,
+    primitives::{Address, B256, PrimitiveSignature, keccak256},
+};
+use anyhow::{Context as _, Result, bail};
+use axum::{Json, debug_handler, extract::State, http::StatusCode, response::IntoResponse};
+use chrono::{DateTime, FixedOffset, Utc};
+use futures_util::future::BoxFuture;
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info, instrument};
+use utoipa::ToSchema;
+
+use crate::{
+    api::{ApiError, ErrorBody, JsonBody, error_response},
+    auth::{AuthError, AuthProvider, UserSession},
+    state::AppState,
+};
+
+/// Key ID in the header of the session tokens issued after a Sign-In With Ethereum.
+const SESSION_KEY_ID: &str = "pokerd-siwe";
+
+/// Issuer of the session tokens.
+const SESSION_ISSUER: &str = "pokerd";
+
+/// Default validity of the session tokens.
+pub const DEFAULT_SESSION_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// How long a nonce can be used to sign in after it was issued.
+const NONCE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Length of the random part of the nonces, and of the tag authenticating them.
+const NONCE_PART_LEN: usize = 16;
+
+/// Length of a nonce: when it was issued, its random part and its tag.
+const NONCE_LEN: usize = 8 + 2 * NONCE_PART_LEN;
+
+/// Tolerated clock difference with the clients for the `Issued At` field.
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+const HEADER_SUFFIX: &str = " wants you to sign in with your Ethereum account:";
+
+#[derive(Clone)]
+pub struct SiweConfig {
+    /// The domain which the messages must be signed for, e.g. `pokerd.xyz`
+    pub domain: String,
+    pub session_secret: String,
+    pub session_ttl: Duration,
+}
+
+impl fmt::Debug for SiweConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SiweConfig")
+            .field("domain", &self.domain)
+            .field("session_ttl", &self.session_ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SiweConfig {
+    /// Read the config from the environment, returning `None` if `SIWE_DOMAIN` is not set.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(domain) = env::var("SIWE_DOMAIN").ok().filter(|d| !d.is_empty()) else {
+            return Ok(None);
+        };
+        let session_secret =
+            env::var("SIWE_SESSION_SECRET").context("SIWE_SESSION_SECRET environment variable")?;
+        if session_secret.len() < 32 {
+            bail!("SIWE_SESSION_SECRET must be at least 32 characters long");
+        }
+        let session_ttl = env::var("SIWE_SESSION_TTL_SECS")
+            .ok()
+            .map(|ttl| ttl.parse())
+            .transpose()
+            .context("SIWE_SESSION_TTL_SECS environment variable")?
+            .unwrap_or(DEFAULT_SESSION_TTL_SECS);
+        Ok(Some(Self {
+            domain,
+            session_secret,
+            session_ttl: Duration::from_secs(session_ttl),
+        }))
+    }
+}
+
+/// Sign-In With Ethereum (EIP-4361): issues nonces, verifies the signed messages and the session
+/// tokens issued in exchange.
+#[derive(Clone)]
+pub struct Siwe {
+    pub config: SiweConfig,
+    encoding_key: Arc<EncodingKey>,
+    decoding_key: Arc<DecodingKey>,
+
+    /// The key authenticating the nonces, which are not stored until they are used
+    nonce_key: B256,
+
+    /// The nonces which were used and could still be valid, with when they were used
+    used_nonces: Arc<Mutex<HashMap<Vec<u8>, Instant>>>,
+}
+
+impl fmt::Debug for Siwe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Siwe")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Claims of the session tokens issued by [`Siwe`].
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionClaims {
+    #[serde(rename = "iss")]
+    issuer: String,
+    #[serde(rename = "sub")]
+    wallet: Address,
+    #[serde(rename = "sid")]
+    session_id: String,
+    #[serde(rename = "iat")]
+    issued_at: i64,
+    #[serde(rename = "exp")]
+    expiration: i64,
+}
+
+impl Siwe {
+    #[must_use]
+    pub fn new(config: SiweConfig) -> Self {
+        let secret = config.session_secret.as_bytes();
+        Self {
+            encoding_key: Arc::new(EncodingKey::from_secret(secret)),
+            decoding_key: Arc::new(DecodingKey::from_secret(secret)),
+            nonce_key: keccak256([b"pokerd-siwe-nonce:", secret].concat()),
+            config,
+            used_nonces: Arc::default(),
+        }
+    }
+
+    /// Issue a single-use nonce to include in the message to sign.
+    ///
+    /// The nonces carry when they were issued and are authenticated with a tag, so that they only
+    /// need to be remembered once used, and unauthenticated clients can't fill the memory.
+    #[must_use]
+    pub fn issue_nonce(&self) -> String {
+        let mut nonce = Utc::now().timestamp().to_be_bytes().to_vec();
+        nonce.extend_from_slice(&B256::random()[..NONCE_PART_LEN]);
+        let tag = self.nonce_tag(&nonce);
+        nonce.extend_from_slice(&tag[..NONCE_PART_LEN]);
+        hex::encode(nonce)
+    }
+
+    fn nonce_tag(&self, payload: &[u8]) -> B256 {
+        keccak256([self.nonce_key.as_slice(), payload].concat())
+    }
+
+    /// Check that a nonce was issued by [`Siwe::issue_nonce`] and didn't expire, returning its
+    /// bytes, which identify it whatever its hex encoding.
+    fn check_nonce(&self, nonce: &str) -> Result<Vec<u8>, SiweError> {
+        let nonce = hex::decode(nonce).map_err(|_| SiweError::InvalidNonce)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(SiweError::InvalidNonce);
+        }
+        let (payload, tag) = nonce.split_at(NONCE_LEN - NONCE_PART_LEN);
+        let expected = self.nonce_tag(payload);
+        let mismatch = tag
+            .iter()
+            .zip(expected.iter())
+            .fold(0, |mismatch, (a, b)| mismatch | (a ^ b));
+        if mismatch != 0 {
+            return Err(SiweError::InvalidNonce);
+        }
+        let issued_at = i64::from_be_bytes(payload[..8].try_into().expect("8 bytes"));
+        let age = Utc::now().timestamp().saturating_sub(issued_at);
+        if !u64::try_from(age).is_ok_and(|age| age < NONCE_TTL.as_secs()) {
+            return Err(SiweError::InvalidNonce);
+        }
+        Ok(nonce)
+    }
+
+    /// Check a signed EIP-4361 message and issue a session token for its wallet.
+    ///
+    /// The chain ID of the message is checked when the chain of the table is known.
+    pub fn verify(
+        &self,
+        message: &str,
+        signature: &str,
+        chain_id: Option<u64>,
+    ) -> Result<SiweSession, SiweError> {
+        let parsed: SiweMessage = message.parse()?;
+        if parsed.domain != self.config.domain {
+            return Err(SiweError::Rejected(format!(
+                "message is for domain {}",
+                parsed.domain
+            )));
+        }
+        if parsed.version != "1" {
+            return Err(SiweError::Rejected(format!(
+                "unsupported version {}",
+                parsed.version
+            )));
+        }
+        if let Some(chain_id) = chain_id.filter(|id| *id != parsed.chain_id) {
+            return Err(SiweError::Rejected(format!(
+                "message is for chain {}, the table is on chain {chain_id}",
+                parsed.chain_id
+            )));
+        }
+        let now = Utc::now();
+        if parsed.issued_at.timestamp() > now.timestamp() + MAX_CLOCK_SKEW_SECS {
+            return Err(SiweError::Rejected(
+                "message is issued in the future".into(),
+            ));
+        }
+        if parsed.expiration_time.is_some_and(|at| at <= now) {
+            return Err(SiweError::Rejected("message expired".into()));
+        }
+        if parsed.not_before.is_some_and(|at| at > now) {
+            return Err(SiweError::Rejected("message is not valid yet".into()));
+        }
+        let nonce = self.check_nonce(&parsed.nonce)?;
+
+        let signature = hex::decode(signature).map_err(|_| SiweError::InvalidSignature)?;
+        let signature = PrimitiveSignature::try_from(signature.as_slice())
+            .map_err(|_| SiweError::InvalidSignature)?;
+        let signer = signature
+            .recover_address_from_msg(message)
+            .map_err(|_| SiweError::InvalidSignature)?;
+        if signer != parsed.address {
+            return Err(SiweError::InvalidSignature);
+        }
+
+        // only burned once everything else checked out, and remembered until it expires
+        {
+            let mut used = self
+                .used_nonces
+                .lock()
+                .expect("nonces lock should not be poisoned");
+            used.retain(|_, used_at| used_at.elapsed() < NONCE_TTL);
+            if used.insert(nonce, Instant::now()).is_some() {
+                return Err(SiweError::InvalidNonce);
+            }
+        }
+
+        self.issue_session(parsed.address)
+    }
+
+    fn issue_session(&self, wallet: Address) -> Result<SiweSession, SiweError> {
+        let now = Utc::now().timestamp();
+        let expires_at = now.saturating_add_unsigned(self.config.session_ttl.as_secs());
+        let claims = SessionClaims {
+            issuer: SESSION_ISSUER.to_string(),
+            wallet,
+            session_id: hex::encode(B256::random()),
+            issued_at: now,
+            expiration: expires_at,
+        };
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(SESSION_KEY_ID.to_string());
+        let token = encode(&header, &claims, &self.encoding_key).map_err(SiweError::Token)?;
+        info!(?wallet, "signed in with ethereum");
+        Ok(SiweSession {
+            token,
+            wallet,
+            expires_at,
+        })
+    }
+
+    /// Validate a session token issued by [`Siwe::verify`].
+    pub fn validate_session(&self, token: &str) -> Result<UserSession, AuthError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[SESSION_ISSUER]);
+        let claims = decode::<SessionClaims>(token, &self.decoding_key, &validation)
+            .map_err(AuthError::InvalidSession)?
+            .claims;
+        debug!(?claims, "session token validated");
+        Ok(UserSession::new(
+            format!("siwe:{}", claims.wallet),
+            claims.session_id,
+            vec![claims.wallet],
+        ))
+    }
+}
+
+/// Accepts the session tokens issued by [`Siwe`], and passes the other tokens on to the configured
+/// provider.
+#[derive(Debug)]
+pub struct SiweSessions {
+    siwe: Siwe,
+    fallback: Arc<dyn AuthProvider>,
+}
+
+impl SiweSessions {
+    #[must_use]
+    pub fn new(siwe: Siwe, fallback: Arc<dyn AuthProvider>) -> Self {
+        Self { siwe, fallback }
+    }
+}
+
+impl AuthProvider for SiweSessions {
+    fn authenticate<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<UserSession, AuthError>> {
+        let is_session =
+            decode_header(token).is_ok_and(|header| header.kid.as_deref() == Some(SESSION_KEY_ID));
+        if is_session {
+            Box::pin(async move { self.siwe.validate_session(token) })
+        } else {
+            self.fallback.authenticate(token)
+        }
+    }
+}
+
+/// The fields of an EIP-4361 message which are checked by the backend.
+#[derive(Debug)]
+struct SiweMessage {
+    domain: String,
+    address: Address,
+    version: String,
+    chain_id: u64,
+    nonce: String,
+    issued_at: DateTime<FixedOffset>,
+    expiration_time: Option<DateTime<FixedOffset>>,
+    not_before: Option<DateTime<FixedOffset>>,
+}
+
+impl FromStr for SiweMessage {
+    type Err = SiweError;
+
+    fn from_str(message: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| SiweError::InvalidMessage(reason.to_string());
+        let mut lines = message.lines();
+        let domain = lines
+            .next()
+            .and_then(|line| line.strip_suffix(HEADER_SUFFIX))
+            .ok_or_else(|| invalid("missing header"))?;
+        let address = lines
+            .next()
+            .and_then(|line| Address::parse_checksummed(line, None).ok())
+            .ok_or_else(|| invalid("missing or non-checksummed address"))?;
+
+        // the optional statement comes before the URI, and the resources after the other fields
+        let mut fields = HashMap::new();
+        for line in lines
+            .skip_while(|line| !line.starts_with("URI: "))
+            .take_while(|line| *line != "Resources:")
+        {
+            let (key, value) = line
+                .split_once(": ")
+                .ok_or_else(|| invalid("malformed field"))?;
+            if fields.insert(key, value).is_some() {
+                return Err(invalid("duplicate field"));
+            }
+        }
+        let field = |key: &str| {
+            fields
+                .get(key)
+                .copied()
+                .ok_or_else(|| SiweError::InvalidMessage(format!("missing {key}")))
+        };
+        let timestamp = |key: &str| {
+            fields
+                .get(key)
+                .map(|value| DateTime::parse_from_rfc3339(value))
+                .transpose()
+                .map_err(|_| SiweError::InvalidMessage(format!("invalid {key}")))
+        };
+        field("URI")?;
+        let nonce = field("Nonce")?;
+        if nonce.len() < 8 || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid("invalid Nonce"));
+        }
+        Ok(Self {
+            domain: domain.to_string(),
+            address,
+            version: field("Version")?.to_string(),
+            chain_id: field("Chain ID")?
+                .parse()
+                .map_err(|_| invalid("invalid Chain ID"))?,
+            nonce: nonce.to_string(),
+            issued_at: timestamp("Issued At")?.ok_or_else(|| invalid("missing Issued At"))?,
+            expiration_time: timestamp("Expiration Time")?,
+            not_before: timestamp("Not Before")?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SiweNonce {
+    /// The nonce to include in the message, valid for 10 minutes
+    pub nonce: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SiweVerifyRequest {
+    /// The EIP-4361 message, as signed by the wallet
+    pub message: String,
+
+    /// The hex-encoded EIP-191 signature of the message
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SiweSession {
+    /// The bearer token for the authenticated endpoints
+    pub token: String,
+
+    #[schema(value_type = String)]
+    pub wallet: Address,
+
+    /// Unix timestamp at which the token expires
+    pub expires_at: i64,
+}
+
+fn siwe(state: &Arc<RwLock<AppState>>) -> Result<(Siwe, Option<u64>), SiweError> {
+    let state = state.read().expect("state lock should not be poisoned");
+    let siwe = state.siwe.clone().ok_or(SiweError::Disabled)?;
+    Ok((siwe, state.listener.chain_id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/siwe/nonce",
+    tag = "auth",
+    responses(
+        (status = 200, description = "A nonce to sign in with", body = SiweNonce),
+        (status = 404, description = "Sign-In With Ethereum is not enabled", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn nonce(
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<SiweNonce>, SiweError> {
+    info!("endpoint called");
+    let (siwe, _) = siwe(&state)?;
+    Ok(Json(SiweNonce {
+        nonce: siwe.issue_nonce(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/siwe/verify",
+    tag = "auth",
+    request_body = SiweVerifyRequest,
+    responses(
+        (status = 200, description = "The signature is valid, with a session token for the wallet", body = SiweSession),
+        (status = 400, description = "The message is not a valid EIP-4361 message", body = ErrorBody),
+        (status = 401, description = "The signature, nonce or message fields are not valid", body = ErrorBody),
+        (status = 404, description = "Sign-In With Ethereum is not enabled", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn verify(
+    State(state): State<Arc<RwLock<AppState>>>,
+    JsonBody(request): JsonBody<SiweVerifyRequest>,
+) -> Result<Json<SiweSession>, SiweError> {
+    info!("endpoint called");
+    let (siwe, chain_id) = siwe(&state)?;
+    Ok(Json(siwe.verify(
+        &request.message,
+        &request.signature,
+        chain_id,
+    )?))
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum SiweError {
+    #[error("sign-in with ethereum is not enabled")]
+    Disabled,
+
+    #[error("invalid message: {0}")]
+    InvalidMessage(String),
+
+    #[error("message rejected: {0}")]
+    Rejected(String),
+
+    #[error("invalid signature")]
+    InvalidSignature,
+
+    #[error("unknown, used or expired nonce")]
+    InvalidNonce,
+
+    #[error("failed to issue session token: {0}")]
+    Token(jsonwebtoken::errors::Error),
+}
+
+impl ApiError for SiweError {
+    fn status(&self) -> StatusCode {
+        match self {
+            SiweError::Disabled => StatusCode::NOT_FOUND,
+            SiweError::InvalidMessage(_) => StatusCode::BAD_REQUEST,
+            SiweError::Rejected(_) | SiweError::InvalidSignature | SiweError::InvalidNonce => {
+                StatusCode::UNAUTHORIZED
+            }
+            SiweError::Token(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            SiweError::Disabled => "siwe_disabled",
+            SiweError::InvalidMessage(_) => "invalid_siwe_message",
+            SiweError::Rejected(_) => "siwe_message_rejected",
+            SiweError::InvalidSignature => "invalid_signature",
+            SiweError::InvalidNonce => "invalid_nonce",
+            SiweError::Token(_) => "internal_error",
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            SiweError::InvalidMessage(reason) | SiweError::Rejected(reason) => {
+                Some(json!({ "reason": reason }))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for SiweError {
+    fn into_response(self) -> axum::response::Response {
+        error_response(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::signers::{SignerSync as _, local::PrivateKeySigner};
+
+    use super::*;
+
+    const DOMAIN: &str = "pokerd.xyz";
+
+    fn siwe() -> Siwe {
+        Siwe::new(SiweConfig {
+            domain: DOMAIN.to_string(),
+            session_secret: "a session secret of at least 32 chars".to_string(),
+            session_ttl: Duration::from_secs(60 * 60),
+        })
+    }
+
+    fn message(domain: &str, address: &str, chain_id: u64, nonce: &str, extra: &str) -> String {
+        format!(
+            "{domain}{HEADER_SUFFIX}\n{address}\n\nSign in to pokerd\n\nURI: https://{domain}\n\
+             Version: 1\nChain ID: {chain_id}\nNonce: {nonce}\nIssued At: {}{extra}",
+            Utc::now().to_rfc3339()
+        )
+    }
+
+    fn sign(signer: &PrivateKeySigner, message: &str) -> String {
+        let signature = signer.sign_message_sync(message.as_bytes()).unwrap();
+        hex::encode(signature.as_bytes())
+    }
+
+    #[test]
+    fn valid_message() {
+        let siwe = siwe();
+        let signer = PrivateKeySigner::random();
+        let message = message(
+            DOMAIN,
+            &signer.address().to_checksum(None),
+            1,
+            &siwe.issue_nonce(),
+            "",
+        );
+        let session = siwe
+            .verify(&message, &sign(&signer, &message), Some(1))
+            .unwrap();
+        assert_eq!(session.wallet, signer.address());
+        let session = siwe.validate_session(&session.token).unwrap();
+        assert_eq!(session.wallets, vec![signer.address()]);
+    }
+
+    #[test]
+    fn reused_nonce() {
+        let siwe = siwe();
+        let signer = PrivateKeySigner::random();
+        let nonce = siwe.issue_nonce();
+        let message = message(DOMAIN, &signer.address().to_checksum(None), 1, &nonce, "");
+        let signature = sign(&signer, &message);
+        siwe.verify(&message, &signature, Some(1)).unwrap();
+        assert!(matches!(
+            siwe.verify(&message, &signature, Some(1)),
+            Err(SiweError::InvalidNonce)
+        ));
+        // the same nonce with another encoding
+        let message = message.replace(&nonce, &nonce.to_uppercase());
+        assert!(matches!(
+            siwe.verify(&message, &sign(&signer, &message), Some(1)),
+            Err(SiweError::InvalidNonce)
+        ));
+    }
+
+    #[test]
+    fn forged_nonce() {
+        let siwe = siwe();
+        let signer = PrivateKeySigner::random();
+        let address = signer.address().to_checksum(None);
+        let other = Siwe::new(SiweConfig {
+            session_secret: "another secret of at least 32 chars".to_string(),
+            ..siwe.config.clone()
+        });
+        let mut tampered = siwe.issue_nonce();
+        tampered.replace_range(20..21, if &tampered[20..21] == "0" { "1" } else { "0" });
+        for nonce in [
+            other.issue_nonce(),
+            tampered,
+            "abcdef0123456789".to_string(),
+        ] {
+            let message = message(DOMAIN, &address, 1, &nonce, "");
+            assert!(matches!(
+                siwe.verify(&message, &sign(&signer, &message), Some(1)),
+                Err(SiweError::InvalidNonce)
+            ));
+        }
+    }
+
+    #[test]
+    fn wrong_domain() {
+        let siwe = siwe();
+        let signer = PrivateKeySigner::random();
+        let message = message(
+            "evil.xyz",
+            &signer.address().to_checksum(None),
+            1,
+            &siwe.issue_nonce(),
+            "",
+        );
+        assert!(matches!(
+            siwe.verify(&message, &sign(&signer, &message), Some(1)),
+            Err(SiweError::Rejected(_))
+        ));
+    }
+
+    #[test]
+    fn wrong_chain_id() {
+        let siwe = siwe();
+        let signer = PrivateKeySigner::random();
+        let message = message(
+            DOMAIN,
+            &signer.address().to_checksum(None),
+            2,
+            &siwe.issue_nonce(),
+            "",
+        );
+        assert!(matches!(
+            siwe.verify(&message, &sign(&signer, &message), Some(1)),
+            Err(SiweError::Rejected(_))
+        ));
+    }
+
+    #[test]
+    fn expired_message() {
+        let siwe = siwe();
+        let signer = PrivateKeySigner::random();
+        let expired = format!(
+            "\nExpiration Time: {}",
+            (Utc::now() - chrono::Duration::minutes(1)).to_rfc3339()
+        );
+        let message = message(
+            DOMAIN,
+            &signer.address().to_checksum(None),
+            1,
+            &siwe.issue_nonce(),
+            &expired,
+        );
+        assert!(matches!(
+            siwe.verify(&message, &sign(&signer, &message), Some(1)),
+            Err(SiweError::Rejected(_))
+        ));
+    }
+
+    #[test]
+    fn non_checksummed_address() {
+        let address = format!("{:#x}", Address::repeat_byte(0xab));
+        let message = message(DOMAIN, &address, 1, "abcdef0123456789", "");
+        assert!(matches!(
+            message.parse::<SiweMessage>(),
+            Err(SiweError::InvalidMessage(_))
+        ));
+    }
+
+    #[test]
+    fn missing_uri() {
+        let address = Address::repeat_byte(0xab).to_checksum(None);
+        let message = message(DOMAIN, &address, 1, "abcdef0123456789", "").replace("URI", "Url");
+        assert!(matches!(
+            message.parse::<SiweMessage>(),
+            Err(SiweError::InvalidMessage(_))
+        ));
+    }
+}
@@ -1,13 +1,37 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
 use alloy::{
     network::EthereumWallet,
-    primitives::{Address, U256},
+    primitives::{Address, B256, I256, U256},
 };
-use anyhow::{Result, bail};
+use anyhow::{Context as _, Result, bail};
+use chrono::Utc;
 use derive_more::{Deref, Display, From, Into, IsVariant};
 use itertools::Itertools as _;
-use rs_poker::core::{Card, FlatDeck, Hand, Rankable as _};
+use metrics_exporter_prometheus::PrometheusHandle;
+use rs_poker::core::{Card, FlatDeck, Hand};
+use rusqlite::{OptionalExtension as _, params};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use utoipa::ToSchema;
 
-use crate::privy::Privy;
+use crate::{
+    actions::DelegatedWallets,
+    admin::{AdminCommand, AdminConfig},
+    api_keys::ApiKeys,
+    auth::AuthProvider,
+    db::{Store, StoreError},
+    events::TableEvent,
+    health::{ListenerStatus, ReadinessConfig},
+    openapi::CardSchema,
+    policy::PolicyConfig,
+    ranking::rank_hand,
+    rounds::{RoundHistory, RoundPlayer, RoundSummary, ShownHand},
+    sessions::SessionRegistry,
+    signed_request::{ContractSignatures, UsedRequests},
+    siwe::Siwe,
+};
 
 pub const MAX_PLAYERS: usize = 5;
 
@@ -62,7 +86,69 @@ pub enum GamePhase {
     },
 }
 
-#[derive(Debug, Copy, Clone, From, Into, Deref, PartialEq, Eq, PartialOrd, Ord, Display)]
+/// A shuffled deck without the cards which were already dealt.
+fn remaining_deck(dealt: &[Card]) -> FlatDeck {
+    let mut deck = FlatDeck::default(); // already shuffled
+    let cards: Vec<Card> = std::iter::from_fn(|| deck.deal())
+        .filter(|c| !dealt.contains(c))
+        .collect();
+    FlatDeck::from(cards)
+}
+
+impl GamePhase {
+    /// Stable snake case name of the phase, as exposed by the API.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            GamePhase::WaitingForPlayers => "waiting_for_players",
+            GamePhase::WaitingForDealer => "waiting_for_dealer",
+            GamePhase::PreFlop { .. } => "pre_flop",
+            GamePhase::WaitingForFlop { .. } => "waiting_for_flop",
+            GamePhase::Flop { .. } => "flop",
+            GamePhase::WaitingForTurn { .. } => "waiting_for_turn",
+            GamePhase::Turn { .. } => "turn",
+            GamePhase::WaitingForRiver { .. } => "waiting_for_river",
+            GamePhase::River { .. } => "river",
+            GamePhase::WaitingForResult { .. } => "waiting_for_result",
+        }
+    }
+
+    /// Remove a player from the ongoing round, because they folded or left the table.
+    ///
+    /// Nobody is dealt in between the rounds, so players leaving the table then are ignored.
+    pub fn remove_player(&mut self, seat: Seat) {
+        match self {
+            GamePhase::WaitingForPlayers | GamePhase::WaitingForDealer => {}
+            GamePhase::PreFlop { players, .. }
+            | GamePhase::WaitingForFlop { players, .. }
+            | GamePhase::Flop { players, .. }
+            | GamePhase::WaitingForTurn { players, .. }
+            | GamePhase::Turn { players, .. }
+            | GamePhase::WaitingForRiver { players, .. }
+            | GamePhase::River { players, .. }
+            | GamePhase::WaitingForResult { players, .. } => {
+                players.retain(|p| p.seat != seat);
+            }
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    From,
+    Into,
+    Deref,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Display,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
 pub struct Seat(usize);
 
 impl TryFrom<U256> for Seat {
@@ -79,12 +165,163 @@ impl From<Seat> for U256 {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TablePlayer {
+    #[schema(value_type = String)]
     pub address: Address,
     pub seat: Seat,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Bet {
+    /// The wallet address of the player
+    #[schema(value_type = String)]
+    pub address: Address,
+
+    /// The seat ID of the player
+    pub seat: Seat,
+
+    /// The amount which was bet
+    #[schema(value_type = String)]
+    pub amount: U256,
+
+    /// The name of the phase during which the bet was placed
+    pub phase: String,
+}
+
+/// A transaction sent by the dealer.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DealerTx {
+    /// The contract function which was called
+    pub call: String,
+
+    /// The transaction hash
+    #[schema(value_type = String)]
+    pub hash: B256,
+
+    /// Whether the transaction succeeded
+    pub success: bool,
+}
+
+/// Bookkeeping for the ongoing round, as reported by the contract events.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoundInfo {
+    /// The on-chain ID of the round, once it started
+    pub id: Option<U256>,
+
+    /// Unix timestamp at which the cards were dealt
+    pub started_at: Option<i64>,
+
+    /// The players which were dealt into the round
+    pub players: Vec<TablePlayer>,
+
+    /// All the bets placed during the round, in order
+    pub bets: Vec<Bet>,
+
+    /// The transactions sent by the dealer for this round
+    pub dealer_txs: Vec<DealerTx>,
+}
+
+impl RoundInfo {
+    #[must_use]
+    pub fn pot(&self) -> U256 {
+        self.bets.iter().map(|b| b.amount).sum()
+    }
+
+    /// The total amount bet by a seat during the round.
+    #[must_use]
+    pub fn contribution(&self, seat: Seat) -> U256 {
+        self.bets
+            .iter()
+            .filter(|b| b.seat == seat)
+            .map(|b| b.amount)
+            .sum()
+    }
+}
+
+/// Public view of the table, which never includes the hole cards of the players.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TableSnapshot {
+    #[schema(value_type = String)]
+    pub table_address: Address,
+    #[schema(value_type = Option<String>)]
+    pub round_id: Option<U256>,
+    pub phase: &'static str,
+    pub seats: Vec<TablePlayer>,
+    pub in_hand: Vec<Seat>,
+    #[schema(value_type = Vec<CardSchema>)]
+    pub board: Vec<Card>,
+    #[schema(value_type = String)]
+    pub pot: U256,
+    pub bets: Vec<Bet>,
+    pub last_processed_block: u64,
+}
+
+/// State of the dealer persisted on graceful shutdown, so that the ongoing round can be resumed after a restart.
+///
+/// Unlike the other views of the state, it includes the hole cards of the players.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedState {
+    pub table_address: Address,
+    pub last_processed_block: u64,
+    pub table_players: Vec<TablePlayer>,
+    #[serde(default)]
+    pub buy_ins: HashMap<Address, U256>,
+
+    /// The name of the phase, see [`GamePhase::name`]
+    pub phase: String,
+    pub players: Vec<SavedPlayer>,
+    pub board: Vec<Card>,
+    pub round: RoundInfo,
+}
+
+/// Table of the saved dealer state, at most one row per table.
+pub(crate) const DEALER_STATE_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS dealer_state (
+    table_address TEXT PRIMARY KEY,
+    saved_at INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+";
+
+impl Store {
+    /// Save the state of the dealer for a table, replacing any previously saved state.
+    pub fn save_dealer_state(&self, state: &SavedState) -> Result<(), StoreError> {
+        let data = serde_json::to_string(state)?;
+        self.conn().execute(
+            "INSERT OR REPLACE INTO dealer_state (table_address, saved_at, data) VALUES (?1, ?2, ?3)",
+            params![state.table_address.to_string(), Utc::now().timestamp(), data],
+        )?;
+        Ok(())
+    }
+
+    /// Remove and return the saved state of the dealer for a table, so that it is only restored once.
+    pub fn take_dealer_state(&self, table: Address) -> Result<Option<SavedState>, StoreError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let data: Option<String> = tx
+            .query_row(
+                "SELECT data FROM dealer_state WHERE table_address = ?1",
+                params![table.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        tx.execute(
+            "DELETE FROM dealer_state WHERE table_address = ?1",
+            params![table.to_string()],
+        )?;
+        tx.commit()?;
+        Ok(data.map(|data| serde_json::from_str(&data)).transpose()?)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub address: Address,
+    pub seat: Seat,
+    pub starting_hand: Hand,
+}
+
 #[derive(Debug, Clone)]
 pub struct Player {
     /// The wallet address of the player
@@ -99,16 +336,57 @@ pub struct Player {
 
 #[derive(Debug, Clone)]
 pub struct AppState {
-    pub privy: Privy,
+    pub auth: Arc<dyn AuthProvider>,
+
+    /// Set when Sign-In With Ethereum is enabled
+    pub siwe: Option<Siwe>,
+
+    pub sessions: SessionRegistry,
+    pub policy: PolicyConfig,
+
+    /// Set when the players can act through the wallets they delegated to the backend
+    pub delegated_wallets: Option<Arc<dyn DelegatedWallets>>,
+
+    /// The signed hand requests which were already used
+    pub signed_requests: UsedRequests,
+    pub contract_signatures: ContractSignatures,
+    pub api_keys: ApiKeys,
     pub rpc_url: String,
     pub signer: EthereumWallet,
     pub table_address: Address,
     pub table_players: Vec<TablePlayer>,
+
+    /// The buy-in of the seated players, as seen in their `PlayerJoined` event
+    pub buy_ins: HashMap<Address, U256>,
     pub phase: GamePhase,
+    pub round: RoundInfo,
+    pub rounds: RoundHistory,
+    pub store: Store,
     pub last_processed_block: u64,
+    pub events: broadcast::Sender<TableEvent>,
+    pub admin: AdminConfig,
+    pub admin_commands: mpsc::Sender<AdminCommand>,
+
+    /// Whether a new round is started as soon as enough players are seated
+    pub auto_start: bool,
+    pub metrics: PrometheusHandle,
+
+    /// When the contract entered its current phase, as seen by the listener
+    pub phase_started_at: Instant,
+    pub listener: ListenerStatus,
+    pub readiness: ReadinessConfig,
+
+    /// Cancelled when the process is shutting down
+    pub shutdown: CancellationToken,
 }
 
 impl AppState {
+    /// Broadcast a public event to all subscribers.
+    pub fn publish(&self, event: TableEvent) {
+        // an error only means that nobody is currently subscribed
+        let _ = self.events.send(event);
+    }
+
     pub fn set_ready(&mut self) {
         self.phase = GamePhase::WaitingForDealer;
     }
@@ -136,7 +414,11 @@ impl AppState {
                 starting_hand: Hand::new_with_cards((0..2).map(|_| deck.deal().unwrap()).collect()),
             });
         }
+        let seats = players.iter().map(|p| p.seat).collect();
         self.phase = GamePhase::PreFlop { deck, players };
+        self.round.started_at = Some(Utc::now().timestamp());
+        self.round.players = participants.to_vec();
+        self.publish(TableEvent::CardsDealt { seats });
         // TODO: send tx to change phase to `PreFlop`
         Ok(())
     }
@@ -165,6 +447,9 @@ impl AppState {
             players,
             flop: flop.clone(),
         };
+        self.publish(TableEvent::Flop {
+            cards: flop.clone(),
+        });
         Ok(flop)
     }
 
@@ -210,6 +495,7 @@ impl AppState {
             flop,
             turn,
         };
+        self.publish(TableEvent::Turn { card: turn });
         // TODO: send tx to change phase to `Turn`
         Ok(turn)
     }
@@ -264,6 +550,7 @@ impl AppState {
             turn,
             river,
         };
+        self.publish(TableEvent::River { card: river });
         Ok(river)
     }
 
@@ -289,8 +576,7 @@ impl AppState {
         Ok(())
     }
 
-    #[allow(clippy::type_complexity)]
-    pub fn reveal_winner(&mut self) -> Result<(Vec<(Seat, Hand)>, Vec<Seat>)> {
+    pub fn reveal_winner(&mut self) -> Result<(Vec<ShownHand>, Vec<Seat>)> {
         let GamePhase::WaitingForResult {
             players,
             flop,
@@ -301,44 +587,232 @@ impl AppState {
         else {
             bail!("too soon");
         };
+        let mut board: Vec<Card> = flop.iter().collect();
+        board.extend([*turn, *river]);
         // players and hands
-        let hands: Vec<_> = players
+        let ranked: Vec<_> = players
             .iter()
-            .map(|p| (p.seat, p.starting_hand.clone()))
+            .map(|p| {
+                let (rank, ranking) = rank_hand(&p.starting_hand, &board);
+                (p, rank, ranking)
+            })
             .collect();
         // winner(s)
-        let winners: Vec<_> = players
+        let winners: Vec<_> = ranked
             .iter()
-            .map(|p| {
-                let mut hand = p.starting_hand.clone();
-                hand.extend(flop.iter());
-                hand.insert(*turn);
-                hand.insert(*river);
-                (p, hand.rank_five())
-            })
-            .max_set_by_key(|(_, rank)| *rank)
+            .max_set_by_key(|(_, rank, _)| *rank)
+            .into_iter()
+            .map(|(p, _, _)| p.seat)
+            .collect();
+        let hands = ranked
             .into_iter()
-            .map(|(p, _)| p.seat)
+            .map(|(p, _, ranking)| ShownHand {
+                address: p.address,
+                seat: p.seat,
+                cards: p.starting_hand.clone(),
+                ranking,
+            })
             .collect();
         Ok((hands, winners))
     }
 
-    pub fn remove_player(&mut self, seat: Seat) -> Result<()> {
-        let players = match &mut self.phase {
-            GamePhase::WaitingForPlayers | GamePhase::WaitingForDealer => bail!("no players yet"),
-            GamePhase::PreFlop { players, .. }
-            | GamePhase::WaitingForFlop { players, .. }
-            | GamePhase::Flop { players, .. }
-            | GamePhase::WaitingForTurn { players, .. }
-            | GamePhase::Turn { players, .. }
-            | GamePhase::WaitingForRiver { players, .. }
-            | GamePhase::River { players, .. }
-            | GamePhase::WaitingForResult { players, .. } => players,
+    /// Record the result of a showdown and reset the state for the next round.
+    ///
+    /// The gains are indexed by seat, as emitted by the contract.
+    pub fn end_round_with_showdown(&mut self, pot: U256, gains: &[I256]) -> RoundSummary {
+        let board = self.get_board();
+        // if we missed part of the round, there is nothing to show
+        let (hands, winners) = self.reveal_winner().unwrap_or_default();
+        let players = self.round_players(|p| gains.get(*p.seat).copied());
+        self.finish_round(RoundSummary {
+            id: self.round.id,
+            table_address: self.table_address,
+            started_at: self.round.started_at,
+            ended_at: Utc::now().timestamp(),
+            showdown: true,
+            players,
+            board,
+            hands,
+            bets: self.round.bets.clone(),
+            winners,
+            pot,
+            dealer_txs: self.round.dealer_txs.clone(),
+        })
+    }
+
+    /// Record the result of a round where everybody else folded and reset the state for the next round.
+    pub fn end_round_without_showdown(&mut self, winner: Seat, pot: U256) -> RoundSummary {
+        let players = self.round_players(|p| {
+            let contribution = I256::from_raw(self.round.contribution(p.seat));
+            Some(if p.seat == winner {
+                I256::from_raw(pot) - contribution
+            } else {
+                -contribution
+            })
+        });
+        self.finish_round(RoundSummary {
+            id: self.round.id,
+            table_address: self.table_address,
+            started_at: self.round.started_at,
+            ended_at: Utc::now().timestamp(),
+            showdown: false,
+            players,
+            board: self.get_board(),
+            hands: vec![],
+            bets: self.round.bets.clone(),
+            winners: vec![winner],
+            pot,
+            dealer_txs: self.round.dealer_txs.clone(),
+        })
+    }
+
+    fn round_players(&self, gains: impl Fn(&TablePlayer) -> Option<I256>) -> Vec<RoundPlayer> {
+        let remaining = self.get_players();
+        self.round
+            .players
+            .iter()
+            .map(|p| RoundPlayer {
+                address: p.address,
+                seat: p.seat,
+                folded: !remaining.is_some_and(|r| r.iter().any(|r| r.seat == p.seat)),
+                gains: gains(p),
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub fn save_state(&self) -> SavedState {
+        SavedState {
+            table_address: self.table_address,
+            last_processed_block: self.last_processed_block,
+            table_players: self.table_players.clone(),
+            buy_ins: self.buy_ins.clone(),
+            phase: self.phase.name().to_string(),
+            players: self
+                .get_players()
+                .into_iter()
+                .flatten()
+                .map(|p| SavedPlayer {
+                    address: p.address,
+                    seat: p.seat,
+                    starting_hand: p.starting_hand.clone(),
+                })
+                .collect(),
+            board: self.get_board(),
+            round: self.round.clone(),
+        }
+    }
+
+    /// Restore the state saved on shutdown.
+    ///
+    /// The cards which were not dealt yet are shuffled again, which makes no difference to the players.
+    pub fn restore_state(&mut self, saved: SavedState) -> Result<()> {
+        if saved.table_address != self.table_address {
+            bail!("state was saved for table {}", saved.table_address);
+        }
+        let players: Vec<_> = saved
+            .players
+            .into_iter()
+            .map(|p| Player {
+                address: p.address,
+                seat: p.seat,
+                starting_hand: p.starting_hand,
+            })
+            .collect();
+        let board = saved.board;
+        let mut dealt: Vec<Card> = players
+            .iter()
+            .flat_map(|p| p.starting_hand.iter())
+            .collect();
+        dealt.extend(board.iter().copied());
+        let deck = remaining_deck(&dealt);
+        let flop = || -> Result<Hand> {
+            match board.get(..3) {
+                Some(flop) => Ok(Hand::new_with_cards(flop.to_vec())),
+                None => bail!("missing flop"),
+            }
+        };
+        let card = |index: usize| board.get(index).copied().context("missing card");
+        self.phase = match saved.phase.as_str() {
+            "waiting_for_players" => GamePhase::WaitingForPlayers,
+            "waiting_for_dealer" => GamePhase::WaitingForDealer,
+            "pre_flop" => GamePhase::PreFlop { deck, players },
+            "waiting_for_flop" => GamePhase::WaitingForFlop { deck, players },
+            "flop" => GamePhase::Flop {
+                deck,
+                players,
+                flop: flop()?,
+            },
+            "waiting_for_turn" => GamePhase::WaitingForTurn {
+                deck,
+                players,
+                flop: flop()?,
+            },
+            "turn" => GamePhase::Turn {
+                deck,
+                players,
+                flop: flop()?,
+                turn: card(3)?,
+            },
+            "waiting_for_river" => GamePhase::WaitingForRiver {
+                deck,
+                players,
+                flop: flop()?,
+                turn: card(3)?,
+            },
+            "river" => GamePhase::River {
+                deck,
+                players,
+                flop: flop()?,
+                turn: card(3)?,
+                river: card(4)?,
+            },
+            "waiting_for_result" => GamePhase::WaitingForResult {
+                deck,
+                players,
+                flop: flop()?,
+                turn: card(3)?,
+                river: card(4)?,
+            },
+            other => bail!("unknown phase {other}"),
         };
-        players.retain(|p| p.seat != seat);
+        self.table_players = saved.table_players;
+        self.buy_ins = saved.buy_ins;
+        self.round = saved.round;
+        self.last_processed_block = saved.last_processed_block;
         Ok(())
     }
 
+    /// The largest buy-in of the given wallets which are seated at the table, which is what a user has
+    /// at stake.
+    ///
+    /// The buy-in of the players seated before a resync is not known, and counts as the largest
+    /// possible one.
+    #[must_use]
+    pub fn stake_of(&self, wallets: &[Address]) -> U256 {
+        self.table_players
+            .iter()
+            .filter(|p| wallets.contains(&p.address))
+            .map(|p| self.buy_ins.get(&p.address).copied().unwrap_or(U256::MAX))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Drop the current round without recording it, e.g. because it was cancelled on-chain.
+    pub fn cancel_round(&mut self) {
+        self.phase = GamePhase::default();
+        self.round = RoundInfo::default();
+        self.publish(TableEvent::RoundEnded);
+    }
+
+    fn finish_round(&mut self, summary: RoundSummary) -> RoundSummary {
+        self.rounds.push(summary.clone());
+        self.phase = GamePhase::default();
+        self.round = RoundInfo::default();
+        self.publish(TableEvent::RoundEnded);
+        summary
+    }
+
     #[must_use]
     pub fn get_players(&self) -> Option<&Vec<Player>> {
         match &self.phase {
@@ -402,4 +876,91 @@ impl AppState {
             }
         }
     }
+
+    /// The cards which were not dealt yet.
+    #[must_use]
+    pub fn get_deck(&self) -> Option<&FlatDeck> {
+        match &self.phase {
+            GamePhase::WaitingForPlayers | GamePhase::WaitingForDealer => None,
+            GamePhase::PreFlop { deck, .. }
+            | GamePhase::WaitingForFlop { deck, .. }
+            | GamePhase::Flop { deck, .. }
+            | GamePhase::WaitingForTurn { deck, .. }
+            | GamePhase::Turn { deck, .. }
+            | GamePhase::WaitingForRiver { deck, .. }
+            | GamePhase::River { deck, .. }
+            | GamePhase::WaitingForResult { deck, .. } => Some(deck),
+        }
+    }
+
+    /// All the community cards revealed so far.
+    #[must_use]
+    pub fn get_board(&self) -> Vec<Card> {
+        let mut board: Vec<Card> = self
+            .get_flop()
+            .map(|f| f.iter().collect())
+            .unwrap_or_default();
+        board.extend(self.get_turn());
+        board.extend(self.get_river());
+        board
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> TableSnapshot {
+        let mut seats = self.table_players.clone();
+        seats.sort_by_key(|p| p.seat);
+        TableSnapshot {
+            table_address: self.table_address,
+            round_id: self.round.id,
+            phase: self.phase.name(),
+            seats,
+            in_hand: self
+                .get_players()
+                .map(|players| players.iter().map(|p| p.seat).collect())
+                .unwrap_or_default(),
+            board: self.get_board(),
+            pot: self.round.pot(),
+            bets: self.round.bets.clone(),
+            last_processed_block: self.last_processed_block,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(seat: usize, hand: &str) -> Player {
+        Player {
+            address: Address::repeat_byte(u8::try_from(seat).unwrap()),
+            seat: Seat(seat),
+            starting_hand: Hand::new_from_str(hand).unwrap(),
+        }
+    }
+
+    #[test]
+    fn leaving_between_rounds() {
+        let mut phase = GamePhase::WaitingForPlayers;
+        phase.remove_player(Seat(1));
+        assert!(phase.is_waiting_for_players());
+        let mut phase = GamePhase::WaitingForDealer;
+        phase.remove_player(Seat(1));
+        assert!(phase.is_waiting_for_dealer());
+    }
+
+    #[test]
+    fn leaving_during_a_round() {
+        let mut phase = GamePhase::PreFlop {
+            deck: FlatDeck::default(),
+            players: vec![player(0, "AsKd"), player(1, "2c3c")],
+        };
+        phase.remove_player(Seat(0));
+        let GamePhase::PreFlop { players, .. } = &phase else {
+            panic!("expected pre-flop, got {phase:?}");
+        };
+        assert_eq!(
+            players.iter().map(|p| p.seat).collect::<Vec<_>>(),
+            [Seat(1)]
+        );
+    }
 }
@@ -0,0 +1,259 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use alloy::primitives::{Address, I256, U256};
+use axum::{Json, debug_handler, extract::State};
+use serde::Serialize;
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+
+use crate::{
+    api::{ErrorBody, Path},
+    auth::UserSession,
+    db::StoreError,
+    history::HistoryError,
+    rounds::RoundSummary,
+    state::{AppState, Seat},
+};
+
+/// Number of forced bets at the start of a round, the small and big blinds.
+const FORCED_BETS: usize = 2;
+
+/// Aggregated statistics of a wallet, derived from the hand history.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct PlayerStats {
+    #[schema(value_type = String)]
+    pub address: Address,
+
+    /// Number of rounds the player was dealt into
+    pub hands_played: usize,
+
+    /// Fraction of hands in which the player voluntarily put money in the pot pre-flop
+    pub vpip: f64,
+
+    /// Fraction of hands in which the player raised pre-flop
+    pub pfr: f64,
+
+    /// Number of showdowns the player took part in
+    pub showdowns: usize,
+
+    /// Fraction of showdowns won by the player
+    pub showdown_win_rate: f64,
+
+    /// Sum of the gains of the player over all rounds, negative if they lost
+    #[schema(value_type = String)]
+    pub net_gains: I256,
+
+    /// The biggest pot won by the player, only counting their share of a split pot
+    #[schema(value_type = String)]
+    pub biggest_pot: U256,
+}
+
+impl PlayerStats {
+    #[must_use]
+    pub fn from_rounds(address: Address, rounds: &[RoundSummary]) -> Self {
+        let mut stats = Self {
+            address,
+            ..Default::default()
+        };
+        let (mut vpip, mut pfr, mut showdowns_won) = (0usize, 0usize, 0usize);
+        for round in rounds {
+            let Some(player) = round.players.iter().find(|p| p.address == address) else {
+                continue;
+            };
+            stats.hands_played += 1;
+            let (voluntary, raised) = pre_flop_actions(round, player.seat);
+            vpip += usize::from(voluntary);
+            pfr += usize::from(raised);
+            let won = round.winners.contains(&player.seat);
+            if round.showdown && !player.folded {
+                stats.showdowns += 1;
+                showdowns_won += usize::from(won);
+            }
+            if let Some(gains) = player.gains {
+                stats.net_gains += gains;
+            }
+            if won {
+                let share = round.pot / U256::from(round.winners.len());
+                stats.biggest_pot = stats.biggest_pot.max(share);
+            }
+        }
+        stats.vpip = ratio(vpip, stats.hands_played);
+        stats.pfr = ratio(pfr, stats.hands_played);
+        stats.showdown_win_rate = ratio(showdowns_won, stats.showdowns);
+        stats
+    }
+}
+
+/// Whether the seat voluntarily put money in the pot pre-flop, and whether it raised pre-flop.
+///
+/// The contract doesn't mark the blinds, which are the first bets of the round, so these are only
+/// counted towards the amount to call.
+fn pre_flop_actions(round: &RoundSummary, seat: Seat) -> (bool, bool) {
+    let mut totals = BTreeMap::<Seat, U256>::new();
+    let (mut voluntary, mut raised) = (false, false);
+    for (i, bet) in round
+        .bets
+        .iter()
+        .filter(|b| b.phase == "pre_flop")
+        .enumerate()
+    {
+        let highest = totals.values().max().copied().unwrap_or_default();
+        let total = totals.entry(bet.seat).or_default();
+        *total += bet.amount;
+        if bet.seat == seat && i >= FORCED_BETS {
+            voluntary = true;
+            raised |= *total > highest;
+        }
+    }
+    (voluntary, raised)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    count as f64 / total as f64
+}
+
+async fn player_stats_for(
+    state: &Arc<RwLock<AppState>>,
+    address: Address,
+) -> Result<PlayerStats, HistoryError> {
+    let store = {
+        state
+            .read()
+            .expect("state lock should not be poisoned")
+            .store
+            .clone()
+    };
+    let rounds = tokio::task::spawn_blocking(move || store.rounds_for_player(address))
+        .await
+        .map_err(StoreError::from)??;
+    Ok(PlayerStats::from_rounds(address, &rounds))
+}
+
+#[utoipa::path(
+    get,
+    path = "/players/{address}/stats",
+    tag = "stats",
+    params(("address" = String, Path, description = "The wallet address of the player")),
+    responses(
+        (status = 200, description = "Statistics of the player", body = PlayerStats),
+        (status = 500, description = "The hand history is not available", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn player_stats(
+    Path(address): Path<Address>,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<PlayerStats>, HistoryError> {
+    info!("endpoint called");
+    Ok(Json(player_stats_for(&state, address).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/stats",
+    tag = "stats",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Statistics of the authenticated player", body = PlayerStats),
+        (status = 401, description = "Missing or invalid access token", body = ErrorBody),
+        (status = 403, description = "The user is not eligible to play at the table", body = ErrorBody),
+        (status = 500, description = "The hand history is not available", body = ErrorBody),
+        (status = 502, description = "The authentication provider failed", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn my_stats(
+    session: UserSession,
+    State(state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<PlayerStats>, HistoryError> {
+    info!("endpoint called");
+    let wallet = {
+        let state = state.read().expect("state lock should not be poisoned");
+        session.wallet_for(|wallet| state.table_players.iter().any(|p| p.address == wallet))
+    };
+    Ok(Json(player_stats_for(&state, wallet).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rounds::RoundPlayer, state::Bet};
+
+    fn player(seat: usize) -> RoundPlayer {
+        RoundPlayer {
+            address: Address::with_last_byte(u8::try_from(seat).unwrap() + 1),
+            seat: seat.into(),
+            folded: false,
+            gains: None,
+        }
+    }
+
+    fn bet(seat: usize, amount: u64) -> Bet {
+        Bet {
+            address: player(seat).address,
+            seat: seat.into(),
+            amount: U256::from(amount),
+            phase: "pre_flop".to_string(),
+        }
+    }
+
+    fn round(bets: Vec<Bet>, winners: &[usize], pot: u64) -> RoundSummary {
+        RoundSummary {
+            id: None,
+            table_address: Address::ZERO,
+            started_at: None,
+            ended_at: 0,
+            showdown: true,
+            players: (0..3).map(player).collect(),
+            board: vec![],
+            hands: vec![],
+            bets,
+            winners: winners.iter().map(|&seat| seat.into()).collect(),
+            pot: U256::from(pot),
+            dealer_txs: vec![],
+        }
+    }
+
+    #[test]
+    fn blinds_are_not_voluntary() {
+        // seats 0 and 1 post the blinds, seat 2 calls and nobody raises
+        let rounds = [round(
+            vec![bet(0, 1), bet(1, 2), bet(2, 2), bet(0, 1)],
+            &[2],
+            6,
+        )];
+        let small_blind = PlayerStats::from_rounds(player(0).address, &rounds);
+        assert!((small_blind.vpip - 1.0).abs() < f64::EPSILON);
+        assert!(small_blind.pfr.abs() < f64::EPSILON);
+        let big_blind = PlayerStats::from_rounds(player(1).address, &rounds);
+        assert!(big_blind.vpip.abs() < f64::EPSILON);
+        assert!(big_blind.pfr.abs() < f64::EPSILON);
+        let caller = PlayerStats::from_rounds(player(2).address, &rounds);
+        assert!((caller.vpip - 1.0).abs() < f64::EPSILON);
+        assert!(caller.pfr.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn raise_over_the_big_blind() {
+        let rounds = [round(vec![bet(0, 1), bet(1, 2), bet(2, 6)], &[2], 9)];
+        let raiser = PlayerStats::from_rounds(player(2).address, &rounds);
+        assert!((raiser.vpip - 1.0).abs() < f64::EPSILON);
+        assert!((raiser.pfr - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn split_pot_credits_the_share() {
+        let rounds = [round(vec![bet(0, 1), bet(1, 2)], &[0, 1], 100)];
+        let stats = PlayerStats::from_rounds(player(0).address, &rounds);
+        assert_eq!(stats.biggest_pot, U256::from(50));
+    }
+}
@@ -0,0 +1,25 @@
+use std::sync::{Arc, RwLock};
+
+use axum::{Json, debug_handler, extract::State};
+use tracing::{info, instrument};
+
+use crate::state::{AppState, TableSnapshot};
+
+#[utoipa::path(
+    get,
+    path = "/table",
+    tag = "table",
+    responses(
+        (status = 200, description = "Public view of the table", body = TableSnapshot),
+    )
+)]
+#[debug_handler]
+#[instrument]
+pub async fn table(State(state): State<Arc<RwLock<AppState>>>) -> Json<TableSnapshot> {
+    info!("endpoint called");
+    let snapshot = state
+        .read()
+        .expect("state lock should not be poisoned")
+        .snapshot();
+    Json(snapshot)
+}
@@ -0,0 +1,171 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result};
+use axum::{
+    debug_handler,
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, histogram};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use tracing::{info, instrument};
+
+use crate::state::AppState;
+
+/// Number of blocks between the chain head and the last block processed by the listener.
+pub const LISTENER_LAG: &str = "pokerd_listener_lag_blocks";
+
+/// Number of contract logs processed, labelled by `event`.
+pub const LOGS_PROCESSED: &str = "pokerd_logs_processed_total";
+
+/// Number of mined dealer transactions, labelled by `call` and `status`.
+pub const DEALER_TXS: &str = "pokerd_dealer_txs_total";
+
+/// Time from the first submission of a dealer transaction until it is mined.
+pub const DEALER_TX_DURATION: &str = "pokerd_dealer_tx_duration_seconds";
+
+/// Number of dealer transactions re-submitted because they were not mined in time.
+pub const DEALER_TX_RETRIES: &str = "pokerd_dealer_tx_retries_total";
+
+/// Number of dealer transactions given up on.
+pub const DEALER_TX_FAILURES: &str = "pokerd_dealer_tx_failures_total";
+
+/// Gas used by the dealer transactions.
+pub const DEALER_GAS_USED: &str = "pokerd_dealer_gas_used_total";
+
+/// Fees paid for the dealer transactions, in gwei.
+pub const DEALER_FEES: &str = "pokerd_dealer_fees_gwei_total";
+
+/// Number of rounds for which cards were dealt.
+pub const ROUNDS_STARTED: &str = "pokerd_rounds_started_total";
+
+/// Number of rounds which ended, labelled by `showdown`.
+pub const ROUNDS_COMPLETED: &str = "pokerd_rounds_completed_total";
+
+/// Time spent in each phase of the contract, labelled by `phase`.
+pub const PHASE_DURATION: &str = "pokerd_phase_duration_seconds";
+
+/// Number of HTTP requests, labelled by `method`, `route` and `status`.
+pub const HTTP_REQUESTS: &str = "pokerd_http_requests_total";
+
+/// Time to respond to HTTP requests, labelled by `method` and `route`.
+pub const HTTP_REQUEST_DURATION: &str = "pokerd_http_request_duration_seconds";
+
+/// Time to look up a user with the Privy API.
+pub const PRIVY_LOOKUP_DURATION: &str = "pokerd_privy_lookup_duration_seconds";
+
+/// Number of failed user lookups with the Privy API.
+pub const PRIVY_LOOKUP_FAILURES: &str = "pokerd_privy_lookup_failures_total";
+
+/// Number of user sessions whose wallet was found in the cache, saving a Privy API call.
+pub const PRIVY_CACHE_HITS: &str = "pokerd_privy_cache_hits_total";
+
+/// Number of player actions sent from delegated wallets, labelled by `call` and `status`.
+pub const DELEGATED_ACTIONS: &str = "pokerd_delegated_actions_total";
+
+/// Histogram buckets, in seconds, covering quick HTTP responses as well as slow phases.
+const BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+/// Interval at which the histograms are compacted.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Install the global metrics recorder, returning a handle to render the metrics.
+pub fn install() -> Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets(&BUCKETS)
+        .context("setting histogram buckets")?
+        .install_recorder()
+        .context("installing metrics recorder")?;
+
+    describe_gauge!(
+        LISTENER_LAG,
+        "Blocks between the chain head and the last processed block"
+    );
+    describe_counter!(LOGS_PROCESSED, "Contract logs processed per event type");
+    describe_counter!(DEALER_TXS, "Mined dealer transactions per call and status");
+    describe_histogram!(
+        DEALER_TX_DURATION,
+        "Time until a dealer transaction is mined"
+    );
+    describe_counter!(
+        DEALER_TX_RETRIES,
+        "Dealer transactions re-submitted with more gas"
+    );
+    describe_counter!(DEALER_TX_FAILURES, "Dealer transactions given up on");
+    describe_counter!(DEALER_GAS_USED, "Gas used by the dealer transactions");
+    describe_counter!(
+        DEALER_FEES,
+        "Fees paid for the dealer transactions, in gwei"
+    );
+    describe_counter!(ROUNDS_STARTED, "Rounds for which cards were dealt");
+    describe_counter!(
+        ROUNDS_COMPLETED,
+        "Rounds which ended, with or without showdown"
+    );
+    describe_histogram!(PHASE_DURATION, "Time spent in each phase of the contract");
+    describe_counter!(HTTP_REQUESTS, "HTTP requests per route and status");
+    describe_histogram!(HTTP_REQUEST_DURATION, "Time to respond to HTTP requests");
+    describe_histogram!(PRIVY_LOOKUP_DURATION, "Time to look up a user with Privy");
+    describe_counter!(PRIVY_LOOKUP_FAILURES, "Failed user lookups with Privy");
+    describe_counter!(PRIVY_CACHE_HITS, "User sessions found in the cache");
+    describe_counter!(
+        DELEGATED_ACTIONS,
+        "Player actions sent from delegated wallets per call and status"
+    );
+    Ok(handle)
+}
+
+/// Periodically compact the histograms, which is otherwise only done when rendering.
+pub async fn run_upkeep(handle: PrometheusHandle) {
+    let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        handle.run_upkeep();
+    }
+}
+
+/// Count and time the HTTP requests per route.
+pub async fn track_http(
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = matched_path.map_or_else(|| "unmatched".to_string(), |p| p.as_str().to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    counter!(
+        HTTP_REQUESTS,
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => response.status().as_u16().to_string(),
+    )
+    .increment(1);
+    histogram!(HTTP_REQUEST_DURATION, "method" => method, "route" => route)
+        .record(started.elapsed().as_secs_f64());
+    response
+}
+
+#[debug_handler]
+#[instrument]
+pub async fn metrics(State(state): State<Arc<RwLock<AppState>>>) -> impl IntoResponse {
+    info!("endpoint called");
+    let handle = {
+        state
+            .read()
+            .expect("state lock should not be poisoned")
+            .metrics
+            .clone()
+    };
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+}
@@ -0,0 +1,180 @@
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    debug_handler,
+    extract::{
+        State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade, close_code},
+    },
+    response::Response,
+};
+use rs_poker::core::Hand;
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, info, instrument, warn};
+
+use crate::{
+    api::ErrorBody,
+    auth::UserSession,
+    codec::CardFormat,
+    events::TableEvent,
+    sealed::SealedCards,
+    state::{AppState, Seat},
+};
+
+/// Messages which are only sent to the authenticated player.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PrivateMessage {
+    HoleCards {
+        seat: Seat,
+        cards: Hand,
+    },
+
+    /// The hole cards, encrypted to the hand key of the session
+    SealedHoleCards {
+        seat: Seat,
+        sealed: SealedCards,
+    },
+}
+
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "table",
+    security(("bearer" = [])),
+    responses(
+        (status = 101, description = "Upgraded to a websocket streaming the table events and the player's hole cards"),
+        (status = 401, description = "Missing or invalid access token", body = ErrorBody),
+        (status = 403, description = "The user is not eligible to play at the table", body = ErrorBody),
+        (status = 502, description = "The authentication provider failed", body = ErrorBody),
+    )
+)]
+#[debug_handler]
+#[instrument(skip(upgrade))]
+pub async fn ws(
+    session: UserSession,
+    State(state): State<Arc<RwLock<AppState>>>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    info!("endpoint called");
+    // subscribe before upgrading so that no event is missed in between
+    let events = state
+        .read()
+        .expect("state lock should not be poisoned")
+        .events
+        .subscribe();
+    upgrade.on_upgrade(move |socket| serve_socket(socket, session, state, events))
+}
+
+async fn serve_socket(
+    mut socket: WebSocket,
+    session: UserSession,
+    state: Arc<RwLock<AppState>>,
+    mut events: broadcast::Receiver<TableEvent>,
+) {
+    let session_id = session.session_id.as_str();
+    debug!(session_id, "websocket client connected");
+    // the cards might have been dealt before the client connected
+    if send_hole_cards(&mut socket, &state, &session)
+        .await
+        .is_err()
+    {
+        return;
+    }
+    let (shutdown, sessions) = {
+        let state = state.read().expect("state lock should not be poisoned");
+        (state.shutdown.clone(), state.sessions.clone())
+    };
+    let closed = sessions.watch(session_id);
+    loop {
+        tokio::select! {
+            () = shutdown.cancelled() => {
+                // clients are expected to reconnect to another instance
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            }
+            () = closed.cancelled() => {
+                debug!(session_id, "session revoked or superseded, closing websocket");
+                let _ = socket
+                    .send(Message::Close(Some(CloseFrame {
+                        code: close_code::POLICY,
+                        reason: "session revoked or superseded".into(),
+                    })))
+                    .await;
+                break;
+            }
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(session_id, skipped, "websocket client lagging behind, skipped events");
+                        // the cards might have been dealt in the skipped events
+                        if send_hole_cards(&mut socket, &state, &session).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if send_json(&mut socket, &event).await.is_err() {
+                    break;
+                }
+                if matches!(event, TableEvent::CardsDealt { .. })
+                    && send_hole_cards(&mut socket, &state, &session).await.is_err()
+                {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(_)) => {} // clients are not expected to send anything
+                }
+            }
+        }
+    }
+    sessions.unwatch(session_id, &closed);
+    debug!(session_id, "websocket client disconnected");
+}
+
+fn hole_cards(state: &Arc<RwLock<AppState>>, session: &UserSession) -> Option<PrivateMessage> {
+    let state = state.read().expect("state lock should not be poisoned");
+    let players = state.get_players()?;
+    // only resolved now, the user might have been seated with another wallet since connecting
+    let wallet = session.wallet_for(|wallet| players.iter().any(|p| p.address == wallet));
+    let player = players.iter().find(|p| p.address == wallet)?;
+    let Some(key) = state.sessions.hand_key(&session.session_id) else {
+        return Some(PrivateMessage::HoleCards {
+            seat: player.seat,
+            cards: player.starting_hand.clone(),
+        });
+    };
+    match key.seal_cards(player.starting_hand.iter(), CardFormat::default()) {
+        Ok(sealed) => Some(PrivateMessage::SealedHoleCards {
+            seat: player.seat,
+            sealed,
+        }),
+        Err(err) => {
+            // never fall back to plaintext once the session asked for encrypted cards
+            warn!(?wallet, ?err, "failed to seal hole cards");
+            None
+        }
+    }
+}
+
+async fn send_hole_cards(
+    socket: &mut WebSocket,
+    state: &Arc<RwLock<AppState>>,
+    session: &UserSession,
+) -> Result<(), axum::Error> {
+    match hole_cards(state, session) {
+        Some(message) => send_json(socket, &message).await,
+        None => Ok(()),
+    }
+}
+
+async fn send_json(socket: &mut WebSocket, message: &impl Serialize) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).expect("message should serialize to JSON");
+    socket.send(Message::Text(text.into())).await
+}